@@ -0,0 +1,20 @@
+use std::fmt;
+
+/// What happens with runs which were missed while Chronos was stopped or the machine was suspended
+#[derive(PartialEq, Clone, Copy)]
+pub enum CatchupPolicy {
+    None,
+    Once,
+    All,
+}
+
+impl fmt::Display for CatchupPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let printable = match *self {
+            CatchupPolicy::None => "none",
+            CatchupPolicy::Once => "once",
+            CatchupPolicy::All => "all",
+        };
+        write!(f, "{}", printable)
+    }
+}
@@ -0,0 +1,33 @@
+use std::fmt;
+
+use crate::enums::execution_status::ExecutionStatus;
+
+/// Which result of the preceding timer triggers the dependent timer
+#[derive(PartialEq, Clone, Copy)]
+pub enum DependencyCondition {
+    Success,
+    Failure,
+    Any,
+}
+
+impl DependencyCondition {
+    /// Check that the status of the finished run triggers the dependent timer, timed out run is a failure
+    pub fn matches(&self, status: &ExecutionStatus) -> bool {
+        return match self {
+            DependencyCondition::Success => *status == ExecutionStatus::Ok,
+            DependencyCondition::Failure => *status != ExecutionStatus::Ok,
+            DependencyCondition::Any => true,
+        };
+    }
+}
+
+impl fmt::Display for DependencyCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let printable = match *self {
+            DependencyCondition::Success => "success",
+            DependencyCondition::Failure => "failure",
+            DependencyCondition::Any => "any",
+        };
+        write!(f, "{}", printable)
+    }
+}
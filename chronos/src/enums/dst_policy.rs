@@ -0,0 +1,49 @@
+use std::fmt;
+
+use chrono::{Duration, LocalResult, NaiveDateTime, Offset, TimeZone};
+
+/// What happens with a local time which does not exist, because clock is moved forward due to DST change
+/// - Skip: timer does not run at that day
+/// - Shift: time is moved forward by the length of the gap, e.g. 02:30 becomes 03:30
+#[derive(PartialEq, Clone, Copy)]
+pub enum DstPolicy {
+    Skip,
+    Shift,
+}
+
+impl DstPolicy {
+    /// Convert local date and time to seconds since UNIX_EPOCH
+    ///
+    /// If local time exists twice (clock is moved backward), then the first one is used.
+    /// If it does not exist, then the result depends on the policy.
+    pub fn resolve<Tz: TimeZone>(&self, tz: &Tz, local: &NaiveDateTime) -> Option<u64> {
+        let time = match tz.from_local_datetime(local) {
+            LocalResult::Single(t) => t.timestamp(),
+            LocalResult::Ambiguous(earliest, _) => earliest.timestamp(),
+            LocalResult::None => match self {
+                DstPolicy::Skip => return None,
+                DstPolicy::Shift => {
+                    // Use the offset which was valid before the gap, it results the same distance after the gap
+                    let before = tz.from_utc_datetime(&(*local - Duration::days(1)));
+                    let offset = before.offset().fix().local_minus_utc();
+                    (*local - Duration::seconds(offset as i64)).and_utc().timestamp()
+                }
+            },
+        };
+
+        if time < 0 {
+            return None;
+        }
+        return Some(time as u64);
+    }
+}
+
+impl fmt::Display for DstPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let printable = match *self {
+            DstPolicy::Skip => "skip",
+            DstPolicy::Shift => "shift",
+        };
+        write!(f, "{}", printable)
+    }
+}
@@ -0,0 +1,20 @@
+use std::fmt;
+
+/// Final status of a command execution, its text is sent to Hermes
+#[derive(PartialEq, Clone, Copy)]
+pub enum ExecutionStatus {
+    Ok,
+    Failed,
+    TimedOut,
+}
+
+impl fmt::Display for ExecutionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let printable = match *self {
+            ExecutionStatus::Ok => "OK",
+            ExecutionStatus::Failed => "NOK",
+            ExecutionStatus::TimedOut => "TIMEOUT",
+        };
+        write!(f, "{}", printable)
+    }
+}
@@ -1,2 +1,9 @@
 pub mod timer_types;
-pub mod command_output_type;
\ No newline at end of file
+pub mod command_output_type;
+pub mod execution_status;
+pub mod overlap_policy;
+pub mod catchup_policy;
+pub mod dst_policy;
+pub mod dependency_condition;
+pub mod retry_backoff;
+pub mod reload_action;
\ No newline at end of file
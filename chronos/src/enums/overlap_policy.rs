@@ -0,0 +1,22 @@
+use std::fmt;
+
+/// What happens when timer should run but its previous run is still active
+#[derive(PartialEq, Clone, Copy)]
+pub enum OverlapPolicy {
+    Allow,
+    Skip,
+    Queue,
+    Replace,
+}
+
+impl fmt::Display for OverlapPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let printable = match *self {
+            OverlapPolicy::Allow => "allow",
+            OverlapPolicy::Skip => "skip",
+            OverlapPolicy::Queue => "queue",
+            OverlapPolicy::Replace => "replace",
+        };
+        write!(f, "{}", printable)
+    }
+}
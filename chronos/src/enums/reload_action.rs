@@ -0,0 +1,22 @@
+use std::fmt;
+
+/// What has happened with a static timer when it was reconciled with its file
+#[derive(PartialEq, Clone, Copy)]
+pub enum ReloadAction {
+    Added,
+    Updated,
+    Removed,
+    Unchanged,
+}
+
+impl fmt::Display for ReloadAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let printable = match *self {
+            ReloadAction::Added => "added",
+            ReloadAction::Updated => "updated",
+            ReloadAction::Removed => "removed",
+            ReloadAction::Unchanged => "unchanged",
+        };
+        write!(f, "{}", printable)
+    }
+}
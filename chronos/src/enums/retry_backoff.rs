@@ -0,0 +1,18 @@
+use std::fmt;
+
+/// How the delay changes between retry attempts
+#[derive(PartialEq, Clone, Copy)]
+pub enum RetryBackoff {
+    Fixed,
+    Exponential,
+}
+
+impl fmt::Display for RetryBackoff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let printable = match *self {
+            RetryBackoff::Fixed => "fixed",
+            RetryBackoff::Exponential => "exponential",
+        };
+        write!(f, "{}", printable)
+    }
+}
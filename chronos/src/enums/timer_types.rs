@@ -5,6 +5,8 @@ pub enum TimerType {
     Every,
     OneShot,
     At,
+    Cron,
+    After,
 }
 
 impl fmt::Display for TimerType {
@@ -13,6 +15,8 @@ impl fmt::Display for TimerType {
             TimerType::Every => "every",
             TimerType::OneShot => "oneshot",
             TimerType::At => "at",
+            TimerType::Cron => "cron",
+            TimerType::After => "after",
         };
         write!(f, "{}", printable)
     }
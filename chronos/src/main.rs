@@ -1,7 +1,6 @@
-use std::sync::{mpsc, RwLock, Mutex};
+use std::sync::{mpsc, RwLock};
 use std::collections::HashMap;
 use std::process::exit;
-use std::io::Write;
 
 #[macro_use]
 mod macros;
@@ -13,10 +12,13 @@ mod services;
 use structs::timer::Timer;
 
 use crate::enums::timer_types::TimerType;
+use crate::enums::catchup_policy::CatchupPolicy;
 
 static VERSION: &str = "v.0.2.0";
 static VERBOSE: RwLock<bool> = RwLock::new(false);
-static TIMERS: Mutex<Vec<Timer>> = Mutex::new(Vec::new());
+
+/// If two triggers are farther than this (in seconds), then clock has jumped and catch-up is needed
+const CLOCK_JUMP_LIMIT: u64 = 60;
 
 fn main() {
     println!("Version {} is starting...", VERSION);
@@ -56,6 +58,25 @@ fn main() {
         }
     }
 
+    /*-------------------------------------------------------------------------------------------*/
+    /* Set concurrency limits of executor                                                        */
+    /*-------------------------------------------------------------------------------------------*/
+    if let Err(e) = services::executor::init_limits(&config) {
+        eprintln!("{}", e);
+        exit(2);
+    }
+
+    let shutdown_timeout = match config.get("shutdown.timeout") {
+        Some(timeout) => match services::duration::parse_duration(timeout) {
+            Ok(t) => t,
+            Err(e) => {
+                eprintln!("Invalid 'shutdown.timeout' property: {}", e);
+                exit(2);
+            }
+        },
+        None => services::shutdown::DEFAULT_TIMEOUT,
+    };
+
     /*-------------------------------------------------------------------------------------------*/
     /* Check that directories are exist                                                          */
     /*-------------------------------------------------------------------------------------------*/
@@ -66,39 +87,70 @@ fn main() {
     if services::file::check_and_create_dir(config.get("timer.log_dir")) != 0 {
         exit(4);
     }
+    services::executor::set_log_dir(config.get("timer.log_dir").unwrap());
+
+    match config.get("timer.state_dir") {
+        Some(_) => {
+            if services::file::check_and_create_dir(config.get("timer.state_dir")) != 0 {
+                exit(4);
+            }
+        }
+        None => println!("Property 'timer.state_dir' is not specified, dynamic timers are not persisted"),
+    }
+    services::state::init(config.get("timer.state_dir"));
+
+    if config.contains_key("timer.calendar_dir") && services::file::check_and_create_dir(config.get("timer.calendar_dir")) != 0 {
+        exit(4);
+    }
+    services::calendar::init(config.get("timer.calendar_dir"));
+    services::reload::init(&args[1], config.get("timer.all_dir").unwrap());
 
     /*-------------------------------------------------------------------------------------------*/
     /* Read startup timers and defined them                                                      */
     /*-------------------------------------------------------------------------------------------*/
     {
         let timer_configs = services::file::read_conf_files(config.get("timer.all_dir").unwrap());
-        let mut timers = TIMERS.lock().unwrap();
         for config in timer_configs {
             match Timer::from_config(config) {
-                Ok(timer) => timers.push(timer),
+                Ok(mut timer) => {
+                    services::state::restore_runs(&mut timer);
+                    if let Err(e) = services::registry::insert(timer, |_| ()) {
+                        eprintln!("Failed to load timer: {}", e);
+                    }
+                }
                 Err(e) => eprintln!("Failed to parse timer: {}", e),
             };            
         }
+
+        // Restore dynamic timers which were created before the last stop
+        for timer in services::state::replay() {
+            let id = timer.id.clone();
+            match services::registry::insert(timer, |_| ()) {
+                Ok(_) => println!("Dynamic timer '{}' is restored", id),
+                Err(e) => eprintln!("Dynamic timer '{}' is not restored: {}", id, e),
+            }
+        }
+        services::registry::with_all(services::state::compact);
+        services::state::flush();
     }
-    
+
     /*-------------------------------------------------------------------------------------------*/
-    /* Start a thread which send trigger in every 1 second                                       */
+    /* Watch timer directory and reload the changed timers if required                           */
     /*-------------------------------------------------------------------------------------------*/
-    let (tx, rx) = mpsc::channel::<u64>();
-    std::thread::spawn(move || {
-        let rt =  match tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build() {
-                Ok(rt) => rt,
-                Err(e) => {
-                    panic!("Failed to allocate tokio runtime for timer trigger: {}", e);
-                }
-            };
-        rt.block_on(async move {
-            services::timing::start_trigger_timer(tx).await;
-        });
-    });
-
+    match config.get("timer.watch").map(|x| x.as_str()) {
+        Some("yes") => {
+            if let Err(e) = services::watcher::start(config.get("timer.all_dir").unwrap()) {
+                eprintln!("{}", e);
+                exit(4);
+            }
+        }
+        Some("no") | None => (),
+        Some(other) => {
+            eprintln!("Invalid value of 'timer.watch' property: '{}', it can be yes or no", other);
+            exit(2);
+        }
+    }
+    
     /*-------------------------------------------------------------------------------------------*/
     /* Start gRPC server                                                                         */
     /*-------------------------------------------------------------------------------------------*/
@@ -117,32 +169,23 @@ fn main() {
     /* Allocate a tokio runtime and start Hermes client if required                              */
     /*-------------------------------------------------------------------------------------------*/
     let (hermes_sender, hermes_receiver) = mpsc::channel::<(String, String)>();
-    if let Some(ena) = config.get("hermes.enable") {
-        if ena == "yes" && config.get("hermes.grpc.address").is_some() && config.get("hermes.table").is_some() {
-            println!("Corresponse properties are set to yes, so start Hermes client");
-            let config2 = config.clone();
-            std::thread::spawn(move || {
-                let rt = tokio::runtime::Builder::new_current_thread()
-                    .enable_all()
-                    .build();
-                let rt = match rt {
-                    Ok(rt) => rt,
-                    Err(e) => panic!("Failed to allocated runtime for Hermes client: {}", e),
-                };
-
-                
-
-                rt.block_on(async move {
-                    loop {
-                        let _ = services::hermes_client::start_hermes_client(&config2, &hermes_receiver).await;
-                        eprintln!("Hermes client has failed, try to restart 30 sec later");
-                        tokio::time::sleep(tokio::time::Duration::new(30, 0)).await;
-                    }
-                })
-            });
-        }
+    if services::hermes_client::configure(&config) {
+        println!("Corresponse properties are set to yes, so start Hermes client");
     }
 
+    // Client runs even if Hermes is not enabled, because it can be enabled by reload
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build();
+        let rt = match rt {
+            Ok(rt) => rt,
+            Err(e) => panic!("Failed to allocated runtime for Hermes client: {}", e),
+        };
+
+        rt.block_on(services::hermes_client::run(hermes_receiver));
+    });
+
 
     /*-------------------------------------------------------------------------------------------*/
     /* Allocate runtime to run timer commands                                                    */
@@ -151,77 +194,161 @@ fn main() {
         .enable_all()
         .build()
         .unwrap();
+    services::executor::init_context(&rt, hermes_sender.clone());
+
+    /*-------------------------------------------------------------------------------------------*/
+    /* Handle signals in own runtime, so running commands cannot block them                      */
+    /*-------------------------------------------------------------------------------------------*/
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build();
+        let rt = match rt {
+            Ok(rt) => rt,
+            Err(e) => panic!("Failed to allocated runtime for signal handling: {}", e),
+        };
+
+        rt.block_on(services::signals::handle_signals());
+    });
+
+    /*-------------------------------------------------------------------------------------------*/
+    /* Catch up runs which were missed while Chronos was stopped                                 */
+    /*-------------------------------------------------------------------------------------------*/
+    let mut last_secs = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(n) => n.as_secs(),
+        Err(e) => panic!("Failed for calculate time since UNIX_EPICH: {}", e),
+    };
+
+    // Timers are only changed while the registry is locked, the state is written and the runs are started after that
+    let mut catch_ups: Vec<(Timer, u64, u64)> = Vec::new();
+    services::registry::update_all(|timer| {
+        if timer.catchup == CatchupPolicy::None || timer.r#type == TimerType::OneShot || timer.paused {
+            return;
+        }
+
+        match services::state::last_run(&timer.id) {
+            Some(last_run) => {
+                let (missed, repeat) = services::executor::missed_runs(timer, last_run, last_secs);
+                if missed == 0 {
+                    return;
+                }
+                timer.runs += repeat;
+                services::state::record_runs(timer);
+                // Timer may have done all of its runs
+                services::timing::schedule(timer);
+                catch_ups.push((timer.clone(), missed, repeat));
+            }
+            None => services::state::record_last_run(&timer.id, last_secs),
+        }
+    });
+    services::state::flush();
+
+    for (timer, missed, repeat) in catch_ups {
+        services::executor::catch_up(timer, missed, repeat);
+    }
 
     /*-------------------------------------------------------------------------------------------*/
     /* Start main part of the program, which executes timers accordingly                         */
     /*-------------------------------------------------------------------------------------------*/
     loop {
-        match rx.recv() {
-            Ok(secs) => {
-                verbose_println!("Triggered second: {}", secs);
-                {
-                    let mut timers = TIMERS.lock().unwrap();
-                    let mut remove_index_list: Vec<usize> = Vec::new();
-                    let mut remove_index: usize = 0;
-                    for timer in timers.iter_mut() {
-                        if timer.should_run(secs) {
-                            println!("Execute: {}", timer.id);
-                            let timer2 = timer.clone();
-                            let log_dir = config.get("timer.log_dir").unwrap().clone();
-                            let hermes_sender = hermes_sender.clone();
-                            rt.spawn(async move {
-                                let output = timer2.execute().await;
-                                let log_file = format!("{}/{}.log", log_dir, timer2.id);
-                                let mut file = match std::fs::OpenOptions::new()
-                                    .write(true)
-                                    .create(true)
-                                    .append(true)
-                                    .open(&log_file) {
-                                        Ok(f) => f,
-                                        Err(e) => {
-                                            eprintln!("Failed to open file '{}' to write: {}", log_file, e);
-                                            return;
-                                        }
-                                    };
-                                
-                                let output = match output {
-                                    Some(o) => o,
-                                    None => return,
-                                };
-
-                                for line in output.0 {
-                                    writeln!(&mut file, "{} {} {}", line.time, line.r#type, line.text).unwrap();
-                                }
-
-                                if output.1 == 0 {
-                                    let _ = hermes_sender.send((format!("{}", timer2.id), String::from("OK")));
-                                }
-                                else {
-                                    let _ = hermes_sender.send((format!("{}", timer2.id), String::from("NOK")));
-                                }
-                            });
-
-                            if timer.r#type == TimerType::OneShot {
-                                remove_index_list.push(remove_index);
-                            }
-                            else {
-                                timer.calculate_next_hit();
-                            }
+        let (secs, due) = services::timing::wait_due();
+        if services::shutdown::requested() {
+            break;
+        }
+
+        // Journal grows with every change of dynamic timers, so it is rewritten from time to time
+        if services::state::compaction_due() {
+            services::registry::with_all(services::state::compact);
+            services::state::flush();
+        }
+        if due.is_empty() && secs <= last_secs + CLOCK_JUMP_LIMIT {
+            last_secs = secs;
+            continue;
+        }
+        verbose_println!("Triggered second: {}, due timers: {}", secs, due.len());
+
+        // Clock has jumped forward (e.g. machine was suspended), handle missed runs by catch-up policy
+        let jumped = secs > last_secs + CLOCK_JUMP_LIMIT;
+        if jumped {
+            println!("Clock jump is detected: {} seconds", secs - last_secs);
+        }
+        last_secs = secs;
+
+        for (deadline, id) in due {
+            // Timer is changed in a short critical section, its state is written and its command is started after that.
+            // Result is the timer to run, how many runs are missed and caught up if clock has jumped, and the dates
+            // which are skipped due to calendars.
+            let result = services::registry::update_or_remove(&id, |timer| {
+                // Timer has been changed since the deadline was added, its actual deadline is scheduled too
+                if timer.deadline() != deadline {
+                    return ((None, None, Vec::new()), true);
+                }
+
+                let mut run: Option<Timer> = None;
+                let mut catch_up: Option<(u64, u64)> = None;
+                let mut skipped = Vec::new();
+                if timer.should_run(secs) {
+                    if timer.r#type == TimerType::OneShot {
+                        verbose_println!("main: {}: Type is oneshot so it purged", timer.id);
+                        if timer.dynamic {
+                            services::state::record_purge(&timer.id);
                         }
-                        remove_index += 1;
+                        return ((Some(timer.clone()), None, skipped), false);
                     }
 
-                    for index in remove_index_list {
-                        verbose_println!("main: {}: Type is oneshot so it purged", timers[index].id);
-                        timers.remove(index);
+                    run = Some(timer.clone());
+                    if jumped {
+                        let (missed, repeat) = services::executor::missed_runs(timer, timer.scheduled_hit() - 1, secs);
+                        timer.runs += repeat;
+                        catch_up = Some((missed, repeat));
+                    }
+                    else {
+                        timer.runs += 1;
                     }
+                    skipped = timer.calculate_next_hit_skipping();
+                    services::state::record_runs(timer);
+                }
+
+                // Timers whose validity window has ended or which have done all of their runs are retired
+                if timer.expired(secs) {
+                    println!("Retire: {}: Validity window has ended", timer.id);
                 }
+                else if timer.remaining_runs() == Some(0) {
+                    println!("Retire: {}: All of {} runs are done", timer.id, timer.runs);
+                }
+                else {
+                    services::timing::schedule(timer);
+                    return ((run, catch_up, skipped), true);
+                }
+                if timer.dynamic {
+                    services::state::record_purge(&timer.id);
+                }
+                services::executor::forget(&timer.id);
+                return ((run, catch_up, skipped), false);
+            });
+
+            let (run, catch_up, skipped) = match result {
+                Some(result) => result,
+                None => continue,
+            };
+            services::state::flush();
+
+            for (date, calendar) in skipped {
+                println!("Skip: {}: {} is excluded by calendar '{}'", id, date, calendar);
+                services::executor::log(&id, &format!("Runs on {} are skipped, because the date is excluded by calendar '{}'", date, calendar));
             }
-            Err(e) => {
-                eprintln!("Failed to receive trigger: {}", e);
-                exit(8);
+
+            match (run, catch_up) {
+                (Some(timer), Some((missed, repeat))) => services::executor::catch_up(timer, missed, repeat),
+                (Some(timer), None) => services::executor::start_timer(timer),
+                (None, _) => (),
             }
         }
     }
 
+    /*-------------------------------------------------------------------------------------------*/
+    /* Stop scheduling, wait for active runs, flush Hermes statuses and save the state           */
+    /*-------------------------------------------------------------------------------------------*/
+    services::shutdown::shut_down(shutdown_timeout);
+    exit(0);
 }
\ No newline at end of file
@@ -0,0 +1,33 @@
+use std::sync::RwLock;
+
+use crate::structs::calendar::Calendar;
+
+/// Directory where calendar files are, if it is not set, then timers cannot use calendars
+static CALENDAR_DIR: RwLock<Option<String>> = RwLock::new(None);
+
+/// Set the calendar directory, it is called once during startup
+pub fn init(calendar_dir: Option<&String>) {
+    let mut dir = CALENDAR_DIR.write().unwrap();
+    *dir = calendar_dir.cloned();
+}
+
+/// Read and parse `<name>.cal` file from the calendar directory
+pub fn load(name: &str) -> Result<Calendar, String> {
+    let dir = CALENDAR_DIR.read().unwrap();
+    let dir = match dir.as_ref() {
+        Some(d) => d,
+        None => return Err(format!("Calendar '{}' cannot be used, because 'timer.calendar_dir' is not specified", name)),
+    };
+
+    if name.is_empty() || name.contains('/') {
+        return Err(format!("Invalid calendar name: '{}'", name));
+    }
+
+    let path = format!("{}/{}.cal", dir, name);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Failed to read calendar '{}': {}", path, e)),
+    };
+
+    return Calendar::parse(name, &content);
+}
@@ -0,0 +1,130 @@
+// This file is also used by the client to validate durations, so it depends only on the standard library
+use std::time::Duration;
+
+/// Seconds in a day
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Parse duration, it can be in HH:MM:SS format (e.g. `01:30:00`) or a list of number and unit pairs
+/// (e.g. `90s`, `2h30m`, `3d`, `1w`). Units: `w` week, `d` day, `h` hour, `m` minute, `s` second.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    if text.contains(':') {
+        return parse_clock(text);
+    }
+
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        if c.is_whitespace() && number.is_empty() {
+            continue;
+        }
+
+        let unit = match c {
+            'w' => 7 * SECONDS_PER_DAY,
+            'd' => SECONDS_PER_DAY,
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(format!("Invalid duration '{}': unknown unit '{}', it can be w, d, h, m or s", text, c)),
+        };
+
+        let value = match number.parse::<u64>() {
+            Ok(v) => v,
+            Err(_) => return Err(format!("Invalid duration '{}': number is missing before '{}'", text, c)),
+        };
+        number.clear();
+
+        seconds = match value.checked_mul(unit).and_then(|x| x.checked_add(seconds)) {
+            Some(s) => s,
+            None => return Err(format!("Invalid duration '{}': it is too long", text)),
+        };
+    }
+
+    if !number.is_empty() {
+        return Err(format!("Invalid duration '{}': unit is missing after '{}'", text, number));
+    }
+
+    if text.is_empty() {
+        return Err(String::from("Invalid duration: it is empty"));
+    }
+
+    return Ok(Duration::from_secs(seconds));
+}
+
+/// Parse duration in HH:MM:SS format, hours can be more than 23
+fn parse_clock(text: &str) -> Result<Duration, String> {
+    let mut parts: Vec<u64> = Vec::new();
+    for part in text.split(':') {
+        match part.parse::<u64>() {
+            Ok(p) => parts.push(p),
+            Err(_) => return Err(format!("Invalid duration '{}': it must be in HH:MM:SS format", text)),
+        }
+    }
+
+    if parts.len() != 3 {
+        return Err(format!("Invalid duration '{}': it must be in HH:MM:SS format", text));
+    }
+
+    if parts[1] > 59 || parts[2] > 59 {
+        return Err(format!("Invalid duration '{}': minutes and seconds must be between 0 and 59", text));
+    }
+
+    let seconds = parts[0].checked_mul(60 * 60).and_then(|x| x.checked_add(parts[1] * 60 + parts[2]));
+    return match seconds {
+        Some(s) => Ok(Duration::from_secs(s)),
+        None => Err(format!("Invalid duration '{}': it is too long", text)),
+    };
+}
+
+/// Format duration, it is HH:MM:SS if it is shorter than a day, else in the unit format, e.g. `3d2h`
+pub fn format_duration(duration: &Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < SECONDS_PER_DAY {
+        return format!("{:02}:{:02}:{:02}", seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    }
+
+    let mut text = String::new();
+    let mut rest = seconds;
+    for (unit, length) in [('w', 7 * SECONDS_PER_DAY), ('d', SECONDS_PER_DAY), ('h', 3600), ('m', 60), ('s', 1)] {
+        if rest >= length {
+            text += &format!("{}{}", rest / length, unit);
+            rest %= length;
+        }
+    }
+
+    return text;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_units_and_clock_format() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h30m"), Ok(Duration::from_secs(9000)));
+        assert_eq!(parse_duration("3d"), Ok(Duration::from_secs(3 * SECONDS_PER_DAY)));
+        assert_eq!(parse_duration("1w 1d"), Ok(Duration::from_secs(8 * SECONDS_PER_DAY)));
+        assert_eq!(parse_duration("01:30:00"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("36:00:00"), Ok(Duration::from_secs(36 * 3600)));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_durations() {
+        for text in ["", "90", "h", "5x", "1:2", "00:60:00", "aa:bb:cc", "18446744073709551615:00:00", "99999999999999999999w"] {
+            assert!(parse_duration(text).is_err(), "'{}' should be rejected", text);
+        }
+    }
+
+    #[test]
+    fn format_is_compatible_below_a_day() {
+        assert_eq!(format_duration(&Duration::from_secs(5400)), "01:30:00");
+        assert_eq!(format_duration(&Duration::from_secs(3 * SECONDS_PER_DAY + 7200)), "3d2h");
+        assert_eq!(format_duration(&Duration::from_secs(8 * SECONDS_PER_DAY)), "1w1d");
+    }
+}
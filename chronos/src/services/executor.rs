@@ -0,0 +1,589 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::mpsc::Sender;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::enums::command_output_type::CommandOutputType;
+use crate::enums::overlap_policy::OverlapPolicy;
+use crate::enums::catchup_policy::CatchupPolicy;
+use crate::enums::execution_status::ExecutionStatus;
+use crate::structs::command_output::CommandOutput;
+use crate::structs::timer::Timer;
+
+/// Bookkeeping of runs for every timer, key is the timer id
+static RUNS: Mutex<BTreeMap<String, RunState>> = Mutex::new(BTreeMap::new());
+
+/// Limit of commands which can run at once, None means unlimited
+static GLOBAL_SLOTS: RwLock<Option<Arc<Semaphore>>> = RwLock::new(None);
+
+/// Limit of commands which can run at once in a concurrency group, key is the group name
+static GROUP_SLOTS: RwLock<BTreeMap<String, Arc<Semaphore>>> = RwLock::new(BTreeMap::new());
+
+/// Where runs are executed, it is set during startup
+static CONTEXT: Mutex<Option<Context>> = Mutex::new(None);
+
+/// Directory of timer logs, it is set during startup and it can be changed by reload
+static LOG_DIR: RwLock<Option<String>> = RwLock::new(None);
+
+/// Environment of runs:
+/// - handle: runtime where commands are executed
+/// - hermes_sender: channel where statuses are sent to Hermes
+struct Context {
+    handle: tokio::runtime::Handle,
+    hermes_sender: Sender<(String, String)>,
+}
+
+/// Run bookkeeping of a timer:
+/// - next_run_id: identifier of the next run
+/// - active: runs which are in progress
+/// - queued: a run is waiting for the active one (only used by `OverlapPolicy::Queue`)
+/// - skipped: how many runs were skipped due to overlap policy
+/// - replaced: how many runs were killed because a newer run replaced them
+#[derive(Default)]
+pub struct RunState {
+    next_run_id: u64,
+    active: Vec<ActiveRun>,
+    queued: bool,
+    pub skipped: u64,
+    pub replaced: u64,
+}
+
+impl RunState {
+    /// Number of runs which are in progress
+    pub fn running(&self) -> usize {
+        return self.active.len() - self.waiting();
+    }
+
+    /// Number of runs which wait for a free slot
+    pub fn waiting(&self) -> usize {
+        return self.active.iter().filter(|x| x.waiting).count();
+    }
+}
+
+/// A run which is in progress:
+/// - run_id: identifier of run within the timer
+/// - pgid: process group of command, it is known after command is started
+/// - replaced: run was killed because a newer run replaced it
+/// - waiting: run waits for a free slot due to concurrency limits
+/// - stopped: run was killed, because Chronos is stopping
+struct ActiveRun {
+    run_id: u64,
+    pgid: Option<libc::pid_t>,
+    replaced: bool,
+    waiting: bool,
+    stopped: bool,
+}
+
+/// Get counters of a timer, it returns (running, waiting, skipped, replaced)
+pub fn run_counters(id: &str) -> (usize, usize, u64, u64) {
+    let runs = RUNS.lock().unwrap();
+    return match runs.get(id) {
+        Some(state) => (state.running(), state.waiting(), state.skipped, state.replaced),
+        None => (0, 0, 0, 0),
+    };
+}
+
+/// Remove bookkeeping of a purged timer, if it has active runs, then it is removed when they end
+pub fn forget(id: &str) {
+    let mut runs = RUNS.lock().unwrap();
+    if runs.get(id).is_some_and(|state| state.active.is_empty()) {
+        runs.remove(id);
+    }
+}
+
+/// Number of runs which are in progress, also the waiting ones
+pub fn active_runs() -> usize {
+    let runs = RUNS.lock().unwrap();
+    return runs.values().map(|x| x.active.len()).sum();
+}
+
+/// Terminate the process group of every active run, it returns how many runs are affected
+pub fn stop_all() -> usize {
+    let mut runs = RUNS.lock().unwrap();
+    let mut count = 0;
+    for (id, state) in runs.iter_mut() {
+        for run in state.active.iter_mut() {
+            run.stopped = true;
+            count += 1;
+            if let Some(pgid) = run.pgid {
+                println!("Shutdown: {}: Kill process group {}", id, pgid);
+                kill_process_group(id, run.run_id, pgid);
+            }
+        }
+    }
+    return count;
+}
+
+/// Set concurrency limits from `executor.max_concurrent` and `group.<name>.max_concurrent` properties of config
+pub fn init_limits(config: &HashMap<String, String>) -> Result<(), String> {
+    if let Some(limit) = config.get("executor.max_concurrent") {
+        let limit = parse_limit("executor.max_concurrent", limit)?;
+        let mut slots = GLOBAL_SLOTS.write().unwrap();
+        *slots = Some(Arc::new(Semaphore::new(limit)));
+    }
+
+    let mut group_slots = GROUP_SLOTS.write().unwrap();
+    for (key, value) in config {
+        if let Some(group) = key.strip_prefix("group.").and_then(|x| x.strip_suffix(".max_concurrent")) {
+            let limit = parse_limit(key, value)?;
+            group_slots.insert(String::from(group), Arc::new(Semaphore::new(limit)));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Parse a concurrency limit, it must be a positive number
+fn parse_limit(key: &str, value: &str) -> Result<usize, String> {
+    return match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("Property '{}' must be a positive number", key)),
+    };
+}
+
+/// Start the command of the timer on the runtime, according to the overlap policy of the timer
+pub fn start_timer(timer: Timer) {
+    match context() {
+        Some((handle, log_dir, hermes_sender)) => start_runs(&handle, timer, 1, log_dir, hermes_sender),
+        None => eprintln!("Executor is not started yet, {} is not run", timer.id),
+    }
+}
+
+/// Set the environment of runs, it is called once during startup
+pub fn init_context(rt: &tokio::runtime::Runtime, hermes_sender: Sender<(String, String)>) {
+    let mut context = CONTEXT.lock().unwrap();
+    *context = Some(Context {
+        handle: rt.handle().clone(),
+        hermes_sender,
+    });
+}
+
+/// Set the directory of timer logs, runs which are already started keep the old one
+pub fn set_log_dir(log_dir: &str) {
+    let mut dir = LOG_DIR.write().unwrap();
+    *dir = Some(String::from(log_dir));
+}
+
+/// Append a line to the log of timer, e.g. when a run is skipped before it would be started
+pub fn log(id: &str, text: &str) {
+    let log_dir = LOG_DIR.read().unwrap().clone();
+    if let Some(log_dir) = log_dir {
+        write_log(&log_dir, id, vec![info_line(text)]);
+    }
+}
+
+/// Copy of the environment of runs: runtime handle, log directory and Hermes sender
+fn context() -> Option<(tokio::runtime::Handle, String, Sender<(String, String)>)> {
+    let log_dir = LOG_DIR.read().unwrap().clone()?;
+    let context = CONTEXT.lock().unwrap();
+    return context.as_ref().map(|x| (x.handle.clone(), log_dir, x.hermes_sender.clone()));
+}
+
+/// Start a run of timer right now, without changing its schedule
+///
+/// Run is handled like the scheduled ones: overlap policy, logging and Hermes status are the same.
+pub fn trigger(timer: Timer) -> Result<(), String> {
+    let (handle, log_dir, hermes_sender) = match context() {
+        Some(c) => c,
+        None => return Err(String::from("Executor is not started yet")),
+    };
+
+    println!("Trigger: {}: Run is started manually", timer.id);
+    write_log(&log_dir, &timer.id, vec![info_line("Run is started manually")]);
+    start_runs(&handle, timer, 1, log_dir, hermes_sender);
+
+    return Ok(());
+}
+
+/// Count runs which should have happened after `since` until `now`, it returns (missed, repeat) where `repeat`
+/// is how many of them are caught up according to the catch-up policy of timer
+pub fn missed_runs(timer: &Timer, since: u64, now: u64) -> (u64, u64) {
+    let missed = timer.missed_runs(since, now);
+
+    let mut repeat = match timer.catchup {
+        CatchupPolicy::None => 0,
+        CatchupPolicy::Once => 1,
+        CatchupPolicy::All => missed,
+    };
+
+    // Catch-up cannot exceed the run limit of timer
+    if let Some(remaining) = timer.remaining_runs() {
+        repeat = repeat.min(remaining);
+    }
+
+    return (missed, repeat.min(missed));
+}
+
+/// Start the runs which are caught up, they are counted by `missed_runs` before
+pub fn catch_up(timer: Timer, missed: u64, repeat: u64) {
+    if missed == 0 {
+        return;
+    }
+
+    let (handle, log_dir, hermes_sender) = match context() {
+        Some(c) => c,
+        None => {
+            eprintln!("Executor is not started yet, missed runs of {} are not caught up", timer.id);
+            return;
+        }
+    };
+
+    println!("Catch-up: {}: {} missed run(s), policy is {}, start {} run(s)", timer.id, missed, timer.catchup, repeat);
+    write_log(&log_dir, &timer.id, vec![info_line(&format!("Missed {} run(s), catch-up policy is {}, so {} run(s) are started", missed, timer.catchup, repeat))]);
+
+    if repeat > 0 {
+        start_runs(&handle, timer, repeat, log_dir, hermes_sender);
+    }
+}
+
+/// Start the command of the timer `repeat` times after each other, according to the overlap policy of the timer
+fn start_runs(rt: &tokio::runtime::Handle, timer: Timer, repeat: u64, log_dir: String, hermes_sender: Sender<(String, String)>) {
+    if crate::services::shutdown::requested() {
+        println!("Skip: {}: Chronos is stopping, run is not started", timer.id);
+        write_log(&log_dir, &timer.id, vec![info_line("Run is not started, because Chronos is stopping")]);
+        return;
+    }
+
+    let run_id = {
+        let mut runs = RUNS.lock().unwrap();
+        let state = runs.entry(timer.id.clone()).or_default();
+
+        if !state.active.is_empty() {
+            match timer.overlap {
+                OverlapPolicy::Allow => (),
+                OverlapPolicy::Skip => {
+                    state.skipped += 1;
+                    println!("Skip: {}: Previous run is still active, skipped runs: {}", timer.id, state.skipped);
+                    write_log(&log_dir, &timer.id, vec![info_line("Run is skipped, because previous run is still active")]);
+                    return;
+                }
+                OverlapPolicy::Queue => {
+                    if state.queued {
+                        state.skipped += 1;
+                        println!("Skip: {}: A run is already queued, skipped runs: {}", timer.id, state.skipped);
+                        write_log(&log_dir, &timer.id, vec![info_line("Run is skipped, because a run is already queued")]);
+                    }
+                    else {
+                        state.queued = true;
+                        println!("Queue: {}: Previous run is still active, run is queued", timer.id);
+                    }
+                    return;
+                }
+                OverlapPolicy::Replace => {
+                    for run in state.active.iter_mut() {
+                        run.replaced = true;
+                        state.replaced += 1;
+                        if let Some(pgid) = run.pgid {
+                            println!("Replace: {}: Kill process group {} of previous run, replaced runs: {}", timer.id, pgid, state.replaced);
+                            kill_process_group(&timer.id, run.run_id, pgid);
+                        }
+                    }
+                }
+            }
+        }
+
+        register_run(state)
+    };
+
+    rt.spawn(async move {
+        let mut run_id = run_id;
+        let mut repeat = repeat;
+        loop {
+            for _ in 0..repeat {
+                run_command(&timer, run_id, &log_dir, &hermes_sender).await;
+            }
+
+            let purged = crate::services::registry::get(&timer.id).is_none();
+
+            // If a run has been queued meanwhile, then it is started now
+            let mut runs = RUNS.lock().unwrap();
+            let state = runs.entry(timer.id.clone()).or_default();
+            state.active.retain(|x| x.run_id != run_id);
+
+            if state.queued && state.active.is_empty() {
+                state.queued = false;
+                run_id = register_run(state);
+                repeat = 1;
+                println!("Queue: {}: Start queued run", timer.id);
+            }
+            else {
+                // Counters of purged timer are not inherited by a new timer with the same id
+                if purged && state.active.is_empty() {
+                    runs.remove(&timer.id);
+                }
+                break;
+            }
+        }
+    });
+}
+
+/// Register a new active run and return with its id
+fn register_run(state: &mut RunState) -> u64 {
+    let run_id = state.next_run_id;
+    state.next_run_id += 1;
+    state.active.push(ActiveRun {
+        run_id,
+        pgid: None,
+        replaced: false,
+        waiting: false,
+        stopped: false,
+    });
+    return run_id;
+}
+
+/// Execute command of timer, then write its output into the log and send its status to Hermes
+///
+/// Failed command is executed again according to the retry properties of timer, only the status of the
+/// last attempt is sent to Hermes.
+async fn run_command(timer: &Timer, run_id: u64, log_dir: &str, hermes_sender: &Sender<(String, String)>) {
+    let attempts = timer.retries + 1;
+    let mut attempt = 1;
+
+    let status = loop {
+        // Slots are released when the attempt has ended, so other runs can use them while this one waits for retry
+        let _slots = acquire_slots(timer, run_id, log_dir).await;
+
+        // Queued runs and runs which waited for a slot are not started during shutdown
+        if crate::services::shutdown::requested() {
+            println!("Skip: {}: Chronos is stopping, run is not started", timer.id);
+            write_log(log_dir, &timer.id, vec![info_line("Run is not started, because Chronos is stopping")]);
+            return;
+        }
+
+        // Run can be replaced before its command is started, e.g. while it waits for a slot
+        if run_flags(&timer.id, run_id).0 {
+            println!("Replace: {}: Run is replaced by a newer run before it is started", timer.id);
+            write_log(log_dir, &timer.id, vec![info_line("Run is not started, because a newer run replaced it")]);
+            return;
+        }
+
+        println!("Execute: {}", timer.id);
+
+        let start = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+            Ok(n) => n.as_secs(),
+            Err(e) => panic!("Failed for calculate time since UNIX_EPICH: {}", e),
+        };
+
+        // Command blocks its thread, so it runs outside of the runtime workers, else it could starve
+        // the waiting runs and retries, and the concurrency limits could not be reached
+        let command_timer = timer.clone();
+        let output = tokio::task::spawn_blocking(move || {
+            command_timer.execute(|pgid| set_pgid(&command_timer.id, run_id, Some(pgid)))
+        }).await;
+
+        // Process group is reaped, it must not be killed anymore, its id can be reused
+        set_pgid(&timer.id, run_id, None);
+
+        let output = match output {
+            Ok(Some(o)) => o,
+            Ok(None) => return,
+            Err(e) => {
+                eprintln!("Execution of {} has failed: {}", timer.id, e);
+                return;
+            }
+        };
+        verbose_println!("run_command: {}: Exit code: {}, status: {}", timer.id, output.code, output.status);
+
+        if output.status == ExecutionStatus::Ok && timer.catchup != CatchupPolicy::None {
+            crate::services::state::record_last_run(&timer.id, start);
+            crate::services::state::flush();
+        }
+
+        let (replaced, stopped) = run_flags(&timer.id, run_id);
+
+        let mut lines = output.output;
+        if stopped {
+            lines.push(info_line("Run was killed, because Chronos is stopping"));
+        }
+
+        // Replaced run is not reported, the newer run will report the status
+        if replaced {
+            lines.push(info_line("Run was killed, because a newer run replaced it"));
+            write_log(log_dir, &timer.id, lines);
+            return;
+        }
+
+        if output.status != ExecutionStatus::Ok && attempt < attempts && !crate::services::shutdown::requested() {
+            let delay = timer.retry_delay_of(attempt);
+            println!("Retry: {}: Attempt {} of {} has ended with {} status, retry in {} seconds", timer.id, attempt, attempts, output.status, delay.as_secs());
+            lines.push(info_line(&format!("Attempt {} of {} has ended with {} status, retry in {} seconds", attempt, attempts, output.status, delay.as_secs())));
+            write_log(log_dir, &timer.id, lines);
+
+            if !sleep_before_retry(delay).await {
+                println!("Retry: {}: Chronos is stopping, retry is cancelled", timer.id);
+                write_log(log_dir, &timer.id, vec![info_line("Retry is cancelled, because Chronos is stopping")]);
+                break output.status;
+            }
+
+            // Run can be replaced while it waits, then the newer run will report the status
+            if run_flags(&timer.id, run_id).0 {
+                println!("Retry: {}: Run is replaced by a newer run, retry is cancelled", timer.id);
+                write_log(log_dir, &timer.id, vec![info_line("Retry is cancelled, because a newer run replaced it")]);
+                return;
+            }
+            attempt += 1;
+            continue;
+        }
+
+        if attempts > 1 {
+            lines.push(info_line(&format!("Attempt {} of {} has ended with {} status", attempt, attempts, output.status)));
+        }
+        write_log(log_dir, &timer.id, lines);
+
+        break output.status;
+    };
+
+    crate::services::hermes_client::send(hermes_sender, (timer.id.clone(), format!("{}", status)));
+    start_dependents(timer, &status, log_dir, hermes_sender);
+}
+
+/// Sleep before the next attempt, it returns false if shutdown is requested meanwhile
+async fn sleep_before_retry(delay: tokio::time::Duration) -> bool {
+    let until = tokio::time::Instant::now() + delay;
+    while !crate::services::shutdown::requested() {
+        let now = tokio::time::Instant::now();
+        if now >= until {
+            return true;
+        }
+        tokio::time::sleep((until - now).min(tokio::time::Duration::from_secs(1))).await;
+    }
+    return false;
+}
+
+/// Wait for a free slot in the concurrency group of timer and in the executor, if they are limited
+async fn acquire_slots(timer: &Timer, run_id: u64, log_dir: &str) -> Vec<OwnedSemaphorePermit> {
+    let mut limits: Vec<(String, Arc<Semaphore>)> = Vec::new();
+    if let Some(group) = &timer.concurrency_group {
+        if let Some(slots) = GROUP_SLOTS.read().unwrap().get(group) {
+            limits.push((format!("group '{}'", group), slots.clone()));
+        }
+    }
+    if let Some(slots) = GLOBAL_SLOTS.read().unwrap().as_ref() {
+        limits.push((String::from("executor"), slots.clone()));
+    }
+
+    let mut permits: Vec<OwnedSemaphorePermit> = Vec::new();
+    for (name, slots) in limits {
+        if let Ok(permit) = slots.clone().try_acquire_owned() {
+            permits.push(permit);
+            continue;
+        }
+
+        println!("Wait: {}: No free slot in {}, run is waiting", timer.id, name);
+        set_waiting(&timer.id, run_id, true);
+        let begin = std::time::Instant::now();
+
+        // Semaphores are never closed, so acquire cannot fail
+        let permit = slots.acquire_owned().await.unwrap();
+
+        set_waiting(&timer.id, run_id, false);
+        let delay = begin.elapsed().as_secs();
+        println!("Wait: {}: Slot in {} is free after {} seconds", timer.id, name, delay);
+        write_log(log_dir, &timer.id, vec![info_line(&format!("Run has waited {} seconds for a free slot in {}", delay, name))]);
+        permits.push(permit);
+    }
+
+    return permits;
+}
+
+/// Get flags of run, it returns (replaced, stopped)
+fn run_flags(id: &str, run_id: u64) -> (bool, bool) {
+    let runs = RUNS.lock().unwrap();
+    return match runs.get(id).and_then(|state| state.active.iter().find(|x| x.run_id == run_id)) {
+        Some(run) => (run.replaced, run.stopped),
+        None => (false, false),
+    };
+}
+
+/// Set process group of run, None when its command has ended
+fn set_pgid(id: &str, run_id: u64, pgid: Option<libc::pid_t>) {
+    let mut runs = RUNS.lock().unwrap();
+    if let Some(state) = runs.get_mut(id) {
+        if let Some(run) = state.active.iter_mut().find(|x| x.run_id == run_id) {
+            run.pgid = pgid;
+        }
+    }
+}
+
+/// Mark run as waiting or not waiting for a free slot
+fn set_waiting(id: &str, run_id: u64, waiting: bool) {
+    let mut runs = RUNS.lock().unwrap();
+    if let Some(state) = runs.get_mut(id) {
+        if let Some(run) = state.active.iter_mut().find(|x| x.run_id == run_id) {
+            run.waiting = waiting;
+        }
+    }
+}
+
+/// Start timers which run after the finished timer, if the status matches with their condition
+fn start_dependents(timer: &Timer, status: &ExecutionStatus, log_dir: &str, hermes_sender: &Sender<(String, String)>) {
+    let mut dependents: Vec<Timer> = Vec::new();
+    crate::services::registry::update_dependents(&timer.id, |dependent| {
+        if !dependent.on.matches(status) || dependent.remaining_runs() == Some(0) || dependent.paused {
+            return;
+        }
+
+        dependent.runs += 1;
+        crate::services::state::record_runs(dependent);
+        // Timer may have done all of its runs, so it has to be retired
+        crate::services::timing::schedule(dependent);
+        dependents.push(dependent.clone());
+    });
+    crate::services::state::flush();
+
+    for dependent in dependents {
+        println!("Trigger: {}: {} has finished with {} status", dependent.id, timer.id, status);
+        start_runs(&tokio::runtime::Handle::current(), dependent, 1, String::from(log_dir), hermes_sender.clone());
+    }
+}
+
+/// Create a log line with the current time
+fn info_line(text: &str) -> CommandOutput {
+    return CommandOutput {
+        time: crate::services::file::time_is_now(),
+        text: String::from(text),
+        r#type: CommandOutputType::Info,
+    };
+}
+
+/// Append lines to the log file of timer
+fn write_log(log_dir: &str, id: &str, lines: Vec<CommandOutput>) {
+    let log_file = format!("{}/{}.log", log_dir, id);
+    let mut file = match std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_file) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("Failed to open file '{}' to write: {}", log_file, e);
+                return;
+            }
+        };
+
+    for line in lines {
+        if let Err(e) = writeln!(&mut file, "{} {} {}", line.time, line.r#type, line.text) {
+            eprintln!("Failed to write file '{}': {}", log_file, e);
+            return;
+        }
+    }
+}
+
+/// Send SIGTERM to the process group of run, then SIGKILL if the run still owns it after the grace period
+fn kill_process_group(id: &str, run_id: u64, pgid: libc::pid_t) {
+    unsafe { libc::kill(-pgid, libc::SIGTERM) };
+    let id = String::from(id);
+    std::thread::spawn(move || {
+        std::thread::sleep(crate::structs::timer::KILL_GRACE_PERIOD);
+        // Process group is forgotten when the command is reaped, after that its id can belong to another group.
+        // Lock is held while the signal is sent, so the run cannot forget it meanwhile.
+        let runs = RUNS.lock().unwrap();
+        let owned = runs.get(&id)
+            .and_then(|state| state.active.iter().find(|x| x.run_id == run_id))
+            .is_some_and(|run| run.pgid == Some(pgid));
+        if owned {
+            unsafe { libc::kill(-pgid, libc::SIGKILL) };
+        }
+    });
+}
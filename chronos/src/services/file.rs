@@ -92,7 +92,7 @@ pub fn read_buffer<T: Read>(reader: &mut BufReader<T>, out_type: CommandOutputTy
     return messages;
 }
 
-fn time_is_now() -> String {
+pub fn time_is_now() -> String {
     let now = chrono::Local::now();
     return format!("{}-{:02}-{:02} {:02}:{:02}:{:02}", now.year(), now.month(), now.day(), now.hour(), now.minute(), now.second());
 }
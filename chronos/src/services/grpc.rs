@@ -1,21 +1,22 @@
 use std::collections::HashMap;
 use std::str::FromStr;
 
-use chrono::{DateTime, Utc, Local, Datelike, Timelike};
+use chrono::{Local, TimeZone};
 
 use tonic::transport::{Identity, ServerTlsConfig};
 use tonic::{transport::Server, Request, Response, Status};
 
 use chronos::chronos_server::{Chronos, ChronosServer};
-use chronos::{Empty, Timer, TimerList, TimerIdArg, TimerArg};
+use chronos::{Empty, Timer, TimerList, TimerIdArg, TimerArg, ReloadSummary};
 
 mod chronos {
     tonic::include_proto!("chronos");
 }
 
 use crate::VERBOSE;
-use crate::TIMERS;
+use crate::enums::reload_action::ReloadAction;
 use crate::enums::timer_types::TimerType;
+use crate::services::duration::format_duration;
 
 #[derive(Debug, Default)]
 struct ChronosGrpc {
@@ -40,31 +41,47 @@ impl Chronos for ChronosGrpc {
 
     /// A gRPC endpoint for listing currently active timers
     async fn list_active_timers(&self, _request: Request<Empty>) -> Result<Response<TimerList>, Status> {
-        let timers = TIMERS.lock().unwrap();
+        let timers = crate::services::registry::list();
 
         let mut ret_timers: Vec<Timer> = Vec::new();
 
         for timer in timers.iter() {
-            let date = match chrono::NaiveDateTime::from_timestamp_opt(timer.next_hit as i64, 0) {
-                Some(date) => date,
-                None => return Err(Status::internal(String::from("Could not convert next hit time"))),
+            // Timer has no next hit, e.g. it is triggered by another timer
+            let next_hit = if timer.next_hit == u64::MAX {
+                String::from("N/A")
+            }
+            else {
+                match format_time(&Local, timer.next_hit) {
+                    Some(next_hit) => next_hit,
+                    None => return Err(Status::internal(String::from("Could not convert next hit time"))),
+                }
             };
-            let date: DateTime<Utc> = chrono::DateTime::from_utc(date, Utc);
-            let date: DateTime<Local> = chrono::DateTime::from(date);
-            let next_hit = format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", date.year(), date.month(), date.day(), date.hour(), date.minute(), date.second());
 
-            let time = match chrono::NaiveTime::from_num_seconds_from_midnight_opt(timer.interval.as_secs() as u32, 0) {
-                Some(time) => time,
-                None => return Err(Status::internal(String::from("Could not convert next hit time"))),
+            // Next hit is shown in the time zone of timer too, it is the same as above if timer has no time zone
+            let (timezone, next_hit_zone) = match &timer.timezone {
+                Some(tz) if timer.next_hit != u64::MAX => match format_time(tz, timer.next_hit) {
+                    Some(next_hit_zone) => (tz.name().to_string(), next_hit_zone),
+                    None => return Err(Status::internal(String::from("Could not convert next hit time"))),
+                },
+                Some(tz) => (tz.name().to_string(), next_hit.clone()),
+                None => (String::new(), next_hit.clone()),
             };
 
             let interval = if timer.r#type == TimerType::At {
                 String::from("N/A")
             }
+            else if let Some(after) = &timer.after {
+                format!("{} on {}", after, timer.on)
+            }
+            else if let Some(schedule) = &timer.schedule {
+                schedule.expression.clone()
+            }
             else {
-                format!("{:02}:{:02}:{:02}", time.hour(), time.minute(), time.second())
+                format_duration(&timer.interval)
             };
 
+            let (running, waiting, skipped, replaced) = crate::services::executor::run_counters(&timer.id);
+
             let timer_item = Timer {
                 id: timer.id.clone(),
                 r#type: format!("{}", timer.r#type),
@@ -72,7 +89,15 @@ impl Chronos for ChronosGrpc {
                 command: timer.command.join(" "),
                 next_hit: next_hit,
                 days: timer.days.iter().collect(),
-                dynamic: timer.dynamic
+                dynamic: timer.dynamic,
+                running: running as u32,
+                waiting: waiting as u32,
+                skipped,
+                replaced,
+                timezone,
+                next_hit_zone,
+                remaining_runs: timer.remaining_runs().map_or(-1, |x| x as i64),
+                paused: timer.paused,
             };
             ret_timers.push(timer_item);
         }
@@ -98,11 +123,18 @@ impl Chronos for ChronosGrpc {
         let mut ret_timers: Vec<Timer> = Vec::new();
 
         for timer in timers.iter() {
-            let time = match chrono::NaiveTime::from_num_seconds_from_midnight_opt(timer.interval.as_secs() as u32, 0) {
-                Some(time) => time,
-                None => return Err(Status::internal(String::from("Could not convert next hit time"))),
+            let interval = if let Some(schedule) = &timer.schedule {
+                schedule.expression.clone()
+            }
+            else if let Some(after) = &timer.after {
+                format!("{} on {}", after, timer.on)
+            }
+            else if timer.r#type == TimerType::At {
+                timer.times.iter().map(format_duration).collect::<Vec<String>>().join(",")
+            }
+            else {
+                format_duration(&timer.interval)
             };
-            let interval = format!("{:02}:{:02}:{:02}", time.hour(), time.minute(), time.second());
 
             let timer_item = Timer {
                 id: timer.id.clone(),
@@ -112,6 +144,14 @@ impl Chronos for ChronosGrpc {
                 next_hit: String::from("None"),
                 days: timer.days.iter().collect(),
                 dynamic: false,
+                running: 0,
+                waiting: 0,
+                skipped: 0,
+                replaced: 0,
+                timezone: timer.timezone.map(|tz| tz.name().to_string()).unwrap_or_default(),
+                next_hit_zone: String::from("None"),
+                remaining_runs: timer.max_runs.map_or(-1, |x| x as i64),
+                paused: false,
             };
             ret_timers.push(timer_item);
         }
@@ -127,18 +167,20 @@ impl Chronos for ChronosGrpc {
     async fn purge_timer(&self, request: Request<TimerIdArg>) -> Result<Response<Empty>, Status> {
         let id = request.into_inner().id;
 
-        let mut timers = TIMERS.lock().unwrap();
-        let mut remove_index: Option<usize> = None;
-        for i in 0..timers.len() {
-            if timers[i].id == id {
-                remove_index = Some(i);
-                break;
+        // Deadline of purged timer is skipped by the scheduler, because the timer is not found
+        let removed = crate::services::registry::remove(&id, |timer| {
+            if timer.dynamic {
+                crate::services::state::record_purge(&id);
             }
-        }
+            else {
+                crate::services::state::forget_runs(&id);
+            }
+        });
 
-        match remove_index {
-            Some(index) => {
-                timers.remove(index);
+        match removed {
+            Some(_) => {
+                crate::services::state::flush();
+                crate::services::executor::forget(&id);
                 return Ok(Response::new(Empty {}));
             }
             None => {
@@ -147,9 +189,99 @@ impl Chronos for ChronosGrpc {
         }
     }
 
+    /// A gRPC endpoint for running active or static timer right now, its next hit is not changed
+    async fn trigger_timer(&self, request: Request<TimerIdArg>) -> Result<Response<Empty>, Status> {
+        let id = request.into_inner().id;
+        check_id(&id)?;
+
+        let timer = match crate::services::registry::get(&id) {
+            Some(timer) => timer,
+            None => {
+                let path = format!("{}/{}.conf", self.timer_dir, id);
+                let mut result = match crate::services::file::read_conf_file(path.as_str()) {
+                    Ok(conf) => conf,
+                    Err(_) => return Err(Status::not_found(format!("No active or static timer was found with {} id", id))),
+                };
+
+                result.insert(String::from("id"), id.clone());
+
+                match crate::structs::timer::Timer::from_config(result) {
+                    Ok(timer) => timer,
+                    Err(e) => return Err(Status::cancelled(format!("Failed to parse timer: {}", e))),
+                }
+            }
+        };
+
+        if let Err(e) = crate::services::executor::trigger(timer) {
+            return Err(Status::unavailable(e));
+        }
+
+        return Ok(Response::new(Empty {}));
+    }
+
+    /// A gRPC endpoint for pausing active timer, it is kept but it does not run until it is resumed
+    async fn pause_timer(&self, request: Request<TimerIdArg>) -> Result<Response<Empty>, Status> {
+        let id = request.into_inner().id;
+
+        let result = crate::services::registry::update(&id, |timer| {
+            if timer.paused {
+                return Err(Status::failed_precondition(format!("Timer {} is already paused", id)));
+            }
+
+            timer.paused = true;
+            if timer.dynamic {
+                crate::services::state::record_update(timer);
+            }
+            crate::services::timing::schedule(timer);
+            return Ok(());
+        });
+        crate::services::state::flush();
+
+        return match result {
+            Some(Ok(_)) => {
+                println!("Pause: {}: Timer is paused", id);
+                Ok(Response::new(Empty {}))
+            }
+            Some(Err(e)) => Err(e),
+            None => Err(Status::not_found(format!("No active timer was found with {} id", id))),
+        };
+    }
+
+    /// A gRPC endpoint for resuming paused timer, its next hit is calculated from now
+    async fn resume_timer(&self, request: Request<TimerIdArg>) -> Result<Response<Empty>, Status> {
+        let id = request.into_inner().id;
+
+        let result = crate::services::registry::update(&id, |timer| {
+            if !timer.paused {
+                return Err(Status::failed_precondition(format!("Timer {} is not paused", id)));
+            }
+
+            timer.paused = false;
+            if timer.r#type != TimerType::OneShot {
+                timer.calculate_next_hit();
+            }
+            if timer.dynamic {
+                crate::services::state::record_update(timer);
+            }
+            crate::services::timing::schedule(timer);
+            return Ok(());
+        });
+        crate::services::state::flush();
+
+        return match result {
+            Some(Ok(_)) => {
+                println!("Resume: {}: Timer is resumed", id);
+                Ok(Response::new(Empty {}))
+            }
+            Some(Err(e)) => Err(e),
+            None => Err(Status::not_found(format!("No active timer was found with {} id", id))),
+        };
+    }
+
     /// A gRPC endpoint for refreshing timer after file change in all timer directory
     async fn refresh_timer(&self, request: Request<TimerIdArg>) -> Result<Response<Empty>, Status> {
         let id = request.into_inner().id;
+        check_id(&id)?;
 
         let path = format!("{}/{}.conf", self.timer_dir, id);
         let mut result = match crate::services::file::read_conf_file(path.as_str()) {
@@ -159,34 +291,89 @@ impl Chronos for ChronosGrpc {
 
         result.insert(String::from("id"), id.clone());
 
-        let timer = match crate::structs::timer::Timer::from_config(result) {
+        let mut timer = match crate::structs::timer::Timer::from_config(result) {
             Ok(timer) => timer,
             Err(e) => return Err(Status::cancelled(format!("Failed to parse timer: {}", e))),
         };
+        crate::services::state::restore_runs(&mut timer);
+
+        let result = crate::services::registry::upsert(timer, |active_timer, timer| {
+            // Refresh does not resume the timer
+            timer.paused = active_timer.paused;
+            if active_timer.dynamic {
+                crate::services::state::record_purge(&active_timer.id);
+                timer.runs = 0;
+            }
+        });
+        crate::services::state::flush();
 
-        let mut timers = TIMERS.lock().unwrap();
-        
-        for active_timer in timers.iter_mut() {
-            if active_timer.id == id {
-                *active_timer = timer;
-                return Ok(Response::new(Empty {}));
+        return match result {
+            Ok(_) => Ok(Response::new(Empty {})),
+            Err(e) => Err(Status::failed_precondition(e)),
+        };
+    }
+
+    /// A gRPC endpoint for reading main config again and reconciling every static timer with its file
+    async fn reload_config(&self, _request: Request<Empty>) -> Result<Response<ReloadSummary>, Status> {
+        println!("Reload is requested");
+        let results = match tokio::task::spawn_blocking(crate::services::reload::reload_config).await {
+            Ok(Ok(results)) => results,
+            Ok(Err(e)) => {
+                eprintln!("Reload: {}", e);
+                return Err(Status::cancelled(e));
+            }
+            Err(e) => return Err(Status::internal(format!("Reload has failed: {}", e))),
+        };
+        println!("Reload: {}", crate::services::reload::summary(&results));
+
+        let mut summary = ReloadSummary::default();
+        for (id, result) in results {
+            match result {
+                Ok(ReloadAction::Added) => summary.added.push(id),
+                Ok(ReloadAction::Updated) => summary.updated.push(id),
+                Ok(ReloadAction::Removed) => summary.removed.push(id),
+                Ok(ReloadAction::Unchanged) => (),
+                Err(e) => summary.failed.push(format!("{}: {}", id, e)),
             }
         }
 
-        timers.push(timer);
-
-        return Ok(Response::new(Empty {}));
+        return Ok(Response::new(summary));
     }
 
     /// A gRPC endpoint for creating dynamic timer
     async fn create_timer(&self, request: Request<TimerArg>) -> Result<Response<Empty>, Status> {
         let args = request.into_inner();
+        check_id(&args.id)?;
         let mut timer_config: HashMap<String, String> = HashMap::new();
         timer_config.insert(String::from("id"), args.id);
         timer_config.insert(String::from("type"), args.r#type);
         timer_config.insert(String::from("interval"), args.interval);
         timer_config.insert(String::from("command"), args.command);
         timer_config.insert(String::from("days"), args.days);
+        if !args.schedule.is_empty() {
+            timer_config.insert(String::from("schedule"), args.schedule);
+        }
+        if !args.timeout.is_empty() {
+            timer_config.insert(String::from("timeout"), args.timeout);
+        }
+        if !args.timezone.is_empty() {
+            timer_config.insert(String::from("timezone"), args.timezone);
+        }
+        if !args.not_before.is_empty() {
+            timer_config.insert(String::from("not_before"), args.not_before);
+        }
+        if !args.not_after.is_empty() {
+            timer_config.insert(String::from("not_after"), args.not_after);
+        }
+        if !args.max_runs.is_empty() {
+            timer_config.insert(String::from("max_runs"), args.max_runs);
+        }
+        if !args.after.is_empty() {
+            timer_config.insert(String::from("after"), args.after);
+        }
+        if !args.on.is_empty() {
+            timer_config.insert(String::from("on"), args.on);
+        }
 
         let mut timer = match crate::structs::timer::Timer::from_config(timer_config) {
             Ok(timer) => timer,
@@ -194,16 +381,29 @@ impl Chronos for ChronosGrpc {
         };
         timer.dynamic = true;
 
-        let mut timers = TIMERS.lock().unwrap();
-        if !timers.contains(&timer) {
-            timers.push(timer);
-            return Ok(Response::new(Empty {}));
-        }
+        return match crate::services::registry::insert(timer, crate::services::state::record_create) {
+            Ok(_) => {
+                crate::services::state::flush();
+                Ok(Response::new(Empty {}))
+            }
+            Err(e) => Err(Status::already_exists(e)),
+        };
+    }
+}
 
-        return Err(Status::already_exists("Timer id already active"));
+/// Check that timer id can be used as file name, because timer files and logs are named after it
+fn check_id(id: &str) -> Result<(), Status> {
+    if id.is_empty() || id == "." || id == ".." || id.contains('/') || id.contains('\0') {
+        return Err(Status::invalid_argument(format!("Invalid timer id: '{}'", id)));
     }
+    return Ok(());
 }
 
+/// Format time (seconds since UNIX_EPOCH) as local date and time of the time zone
+fn format_time<Tz: TimeZone>(tz: &Tz, time: u64) -> Option<String> {
+    let date = tz.timestamp_opt(time as i64, 0).single()?;
+    return Some(date.naive_local().format("%Y-%m-%d %H:%M:%S").to_string());
+}
 
 /// Start gRPC server, this must be run from a tokio runtime environment
 pub async fn start_server(config: &HashMap<String, String>) -> Result<(), Box<dyn std::error::Error>> {
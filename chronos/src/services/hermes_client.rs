@@ -1,4 +1,8 @@
 use std::collections::HashMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
 
 use tonic::transport::{Channel, Certificate, ClientTlsConfig};
 use tonic::{Request, Response, Status};
@@ -10,7 +14,77 @@ mod hermes {
     tonic::include_proto!("hermes");
 }
 
-pub async fn start_hermes_client(config: &HashMap<String, String>, receiver: &std::sync::mpsc::Receiver<(String, String)>) -> Result<(), Box<dyn std::error::Error>> {
+/// Config of Hermes client, None if Hermes is not enabled
+static SETTINGS: RwLock<Option<HashMap<String, String>>> = RwLock::new(None);
+
+/// Settings have been changed since the client was started, so it has to be restarted
+static CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Number of statuses which are sent to the client, but it has not handled them yet
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// How often the client checks that its settings have been changed while it waits for statuses
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Set the settings of Hermes client from config, it returns that Hermes is enabled
+///
+/// Hermes is enabled if `hermes.enable` is yes, and `hermes.grpc.address` and `hermes.table` are specified.
+pub fn configure(config: &HashMap<String, String>) -> bool {
+    let enabled = config.get("hermes.enable").map(|x| x == "yes").unwrap_or(false)
+        && config.get("hermes.grpc.address").is_some()
+        && config.get("hermes.table").is_some();
+
+    let mut settings = SETTINGS.write().unwrap();
+    *settings = if enabled { Some(config.clone()) } else { None };
+    CHANGED.store(true, Ordering::SeqCst);
+
+    return enabled;
+}
+
+/// Pass a status (timer id and status text) to the client
+pub fn send(sender: &Sender<(String, String)>, message: (String, String)) {
+    PENDING.fetch_add(1, Ordering::SeqCst);
+    if sender.send(message).is_err() {
+        PENDING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Number of statuses which have not been handled by the client yet
+pub fn pending() -> usize {
+    return PENDING.load(Ordering::SeqCst);
+}
+
+/// Send statuses to Hermes with the current settings, client is restarted when its settings are changed,
+/// or 30 seconds later if it has failed. Statuses are dropped while Hermes is not enabled.
+pub async fn run(receiver: Receiver<(String, String)>) {
+    loop {
+        CHANGED.store(false, Ordering::SeqCst);
+        let settings = SETTINGS.read().unwrap().clone();
+
+        match settings {
+            Some(config) => {
+                if let Err(e) = start_hermes_client(&config, &receiver).await {
+                    eprintln!("Hermes client has failed: {}", e);
+                }
+                if !CHANGED.load(Ordering::SeqCst) {
+                    eprintln!("Hermes client has stopped, try to restart 30 sec later");
+                    tokio::time::sleep(tokio::time::Duration::new(30, 0)).await;
+                }
+            }
+            None => {
+                while !CHANGED.load(Ordering::SeqCst) {
+                    if let Ok(message) = receiver.recv_timeout(CHECK_INTERVAL) {
+                        verbose_println!("Hermes is not enabled, drop {:?}", message);
+                        PENDING.fetch_sub(1, Ordering::SeqCst);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Connect to Hermes and send the received statuses, it returns when the settings have been changed
+async fn start_hermes_client(config: &HashMap<String, String>, receiver: &Receiver<(String, String)>) -> Result<(), Box<dyn std::error::Error>> {
     // Address and table are mandatory parameters, they are enough for a simple non-TLS connection
     let addr = config.get("hermes.grpc.address").unwrap();
     let table = config.get("hermes.table").unwrap();
@@ -25,27 +99,22 @@ pub async fn start_hermes_client(config: &HashMap<String, String>, receiver: &st
 
     // Create a gRPC channel for Hermes, TLS version if specified, else non-TLS
     let channel = if tls == "yes" && tls_cert.is_some() && tls_domain.is_some() {
-        let pem = tokio::fs::read(tls_cert.unwrap()).await.unwrap();
+        let pem = tokio::fs::read(tls_cert.unwrap()).await?;
         let ca = Certificate::from_pem(pem);
 
         let tls = ClientTlsConfig::new()
             .ca_certificate(ca)
             .domain_name(tls_domain.unwrap());
 
-        Channel::from_shared(addr.clone())
-            .unwrap()
-            .tls_config(tls)
-            .unwrap()
+        Channel::from_shared(addr.clone())?
+            .tls_config(tls)?
             .connect()
-            .await
-            .unwrap()
+            .await?
     }
     else {
-        Channel::from_shared(addr.clone())
-            .unwrap()
+        Channel::from_shared(addr.clone())?
             .connect()
-            .await
-            .unwrap()
+            .await?
     };
 
     // Get key prefix for hermes
@@ -59,7 +128,19 @@ pub async fn start_hermes_client(config: &HashMap<String, String>, receiver: &st
     println!("Hermes client is ready");
 
     // Waiting for message what has to be send over to Hermes
-    while let Ok(message) = receiver.recv() {
+    loop {
+        let message = match receiver.recv_timeout(CHECK_INTERVAL) {
+            Ok(message) => message,
+            Err(RecvTimeoutError::Timeout) => {
+                if CHANGED.load(Ordering::SeqCst) {
+                    println!("Hermes settings have been changed, restart Hermes client");
+                    return Ok(());
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
         println!("Update Hermes with {:?}", message);
         let key = format!("{}{}", prefix, message.0);
         let pair = SetPair {
@@ -74,6 +155,7 @@ pub async fn start_hermes_client(config: &HashMap<String, String>, receiver: &st
         if let Err(e) = response {
             eprintln!("Failed to update Hermes: {}", e.message());
         }
+        PENDING.fetch_sub(1, Ordering::SeqCst);
     }
 
     return Ok(());
@@ -1,4 +1,13 @@
 pub mod file;
 pub mod timing;
 pub mod grpc;
-pub mod hermes_client;
\ No newline at end of file
+pub mod hermes_client;
+pub mod executor;
+pub mod state;
+pub mod duration;
+pub mod calendar;
+pub mod registry;
+pub mod reload;
+pub mod watcher;
+pub mod signals;
+pub mod shutdown;
\ No newline at end of file
@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use crate::structs::timer::Timer;
+
+/// Active timers. Lock is only held inside the functions of this module, so the scheduler and the gRPC endpoints
+/// do not block each other for long.
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry::new());
+
+/// Active timers with their index:
+/// - timers: active timers, key is the timer id
+/// - dependents: id of timers which run after a timer (see `after` property), key is the id of the preceding timer
+struct Registry {
+    timers: BTreeMap<String, Timer>,
+    dependents: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Registry {
+    const fn new() -> Self {
+        return Registry {
+            timers: BTreeMap::new(),
+            dependents: BTreeMap::new(),
+        };
+    }
+
+    /// Add timer or replace the one with the same id, and index it
+    fn add(&mut self, timer: Timer) {
+        self.take(&timer.id);
+        if let Some(after) = &timer.after {
+            self.dependents.entry(after.clone()).or_default().insert(timer.id.clone());
+        }
+        self.timers.insert(timer.id.clone(), timer);
+    }
+
+    /// Remove timer and its index, it returns the removed timer
+    fn take(&mut self, id: &str) -> Option<Timer> {
+        let timer = self.timers.remove(id)?;
+        if let Some(after) = &timer.after {
+            if let Some(dependents) = self.dependents.get_mut(after) {
+                dependents.remove(id);
+                if dependents.is_empty() {
+                    self.dependents.remove(after);
+                }
+            }
+        }
+        return Some(timer);
+    }
+}
+
+/// Add a new timer and schedule it, error if a timer with the same id is already active or the timer
+/// would depend on itself, `on_insert` is called with the timer before it is added
+pub fn insert<F: FnOnce(&Timer)>(timer: Timer, on_insert: F) -> Result<(), String> {
+    let mut registry = REGISTRY.lock().unwrap();
+    if registry.timers.contains_key(&timer.id) {
+        return Err(format!("Timer '{}' is already active", timer.id));
+    }
+    check_dependency(&registry.timers, &timer)?;
+
+    on_insert(&timer);
+    crate::services::timing::schedule(&timer);
+    registry.add(timer);
+    return Ok(());
+}
+
+/// Add a timer or replace the active one with the same id and schedule it, error if the timer would
+/// depend on itself, `on_replace` is called with the old and the new timer before the replacement
+pub fn upsert<F: FnOnce(&Timer, &mut Timer)>(mut timer: Timer, on_replace: F) -> Result<(), String> {
+    let mut registry = REGISTRY.lock().unwrap();
+    check_dependency(&registry.timers, &timer)?;
+    if let Some(old) = registry.timers.get(&timer.id) {
+        on_replace(old, &mut timer);
+    }
+
+    crate::services::timing::schedule(&timer);
+    registry.add(timer);
+    return Ok(());
+}
+
+/// Check that the `after` chain of timer does not lead back to the timer, else they would trigger each other endlessly
+fn check_dependency(timers: &BTreeMap<String, Timer>, timer: &Timer) -> Result<(), String> {
+    let mut chain = vec![timer.id.clone()];
+    let mut after = timer.after.clone();
+
+    // Active timers do not form a loop, so the chain ends or returns to the timer within this many steps
+    while let Some(id) = after {
+        chain.push(id.clone());
+        if id == timer.id {
+            return Err(format!("Timer '{}' would run after itself: {}", timer.id, chain.join(" -> ")));
+        }
+        if chain.len() > timers.len() + 1 {
+            break;
+        }
+        after = timers.get(&id).and_then(|x| x.after.clone());
+    }
+
+    return Ok(());
+}
+
+/// Remove timer, it returns the removed timer, `on_remove` is called with the timer before it is removed
+pub fn remove<F: FnOnce(&Timer)>(id: &str, on_remove: F) -> Option<Timer> {
+    let mut registry = REGISTRY.lock().unwrap();
+    on_remove(registry.timers.get(id)?);
+    return registry.take(id);
+}
+
+/// Get a copy of timer
+pub fn get(id: &str) -> Option<Timer> {
+    let registry = REGISTRY.lock().unwrap();
+    return registry.timers.get(id).cloned();
+}
+
+/// Get a copy of every active timer, ordered by id
+pub fn list() -> Vec<Timer> {
+    let registry = REGISTRY.lock().unwrap();
+    return registry.timers.values().cloned().collect();
+}
+
+/// Change timer with `f`, None if timer is not active
+///
+/// If `f` changes the deadline of timer, then it has to schedule the timer. It must not change `after` property.
+pub fn update<R, F: FnOnce(&mut Timer) -> R>(id: &str, f: F) -> Option<R> {
+    let mut registry = REGISTRY.lock().unwrap();
+    return registry.timers.get_mut(id).map(f);
+}
+
+/// Change timer with `f`, then remove it if `f` returns false beside its result, None if timer is not active
+pub fn update_or_remove<R, F: FnOnce(&mut Timer) -> (R, bool)>(id: &str, f: F) -> Option<R> {
+    let mut registry = REGISTRY.lock().unwrap();
+    let timer = registry.timers.get_mut(id)?;
+
+    let (result, keep) = f(timer);
+    if !keep {
+        registry.take(id);
+    }
+
+    return Some(result);
+}
+
+/// Call `f` with a copy of every active timer, ordered by id, timers cannot be changed until it returns
+pub fn with_all<R, F: FnOnce(&[Timer]) -> R>(f: F) -> R {
+    let registry = REGISTRY.lock().unwrap();
+    let list: Vec<Timer> = registry.timers.values().cloned().collect();
+    return f(&list);
+}
+
+/// Call `f` with every active timer, it must not change `after` property
+pub fn update_all<F: FnMut(&mut Timer)>(f: F) {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.timers.values_mut().for_each(f);
+}
+
+/// Call `f` with every active timer which runs after the timer, it must not change `after` property
+pub fn update_dependents<F: FnMut(&mut Timer)>(id: &str, mut f: F) {
+    let mut registry = REGISTRY.lock().unwrap();
+    let registry = &mut *registry;
+    if let Some(dependents) = registry.dependents.get(id) {
+        for dependent in dependents {
+            if let Some(timer) = registry.timers.get_mut(dependent) {
+                f(timer);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::timer_types::TimerType;
+    use tokio::time::Duration;
+
+    fn after(id: &str, after: &str) -> Timer {
+        let mut timer = Timer::new(String::from(id), TimerType::After, Duration::from_secs(0), vec![String::from("true")], vec!['X'; 7], false, None);
+        timer.after = Some(String::from(after));
+        return timer;
+    }
+
+    #[test]
+    fn dependency_loop_is_rejected() {
+        let mut timers: BTreeMap<String, Timer> = BTreeMap::new();
+        timers.insert(String::from("a"), after("a", "c"));
+        timers.insert(String::from("b"), after("b", "a"));
+
+        assert!(check_dependency(&timers, &after("d", "b")).is_ok());
+        assert_eq!(check_dependency(&timers, &after("c", "b")), Err(String::from("Timer 'c' would run after itself: c -> b -> a -> c")));
+
+        // Replaced timer is checked with its new dependency
+        assert!(check_dependency(&timers, &after("a", "d")).is_ok());
+        assert!(check_dependency(&timers, &after("a", "b")).is_err());
+    }
+
+    #[test]
+    fn dependents_follow_replaced_and_removed_timers() {
+        let mut registry = Registry::new();
+        registry.add(after("a", "x"));
+        registry.add(after("b", "x"));
+        registry.add(after("a", "y"));
+        assert_eq!(registry.dependents.get("x"), Some(&BTreeSet::from([String::from("b")])));
+        assert_eq!(registry.dependents.get("y"), Some(&BTreeSet::from([String::from("a")])));
+
+        registry.take("b");
+        assert!(registry.dependents.get("x").is_none());
+    }
+}
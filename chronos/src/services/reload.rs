@@ -0,0 +1,187 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::RwLock;
+
+use crate::enums::reload_action::ReloadAction;
+use crate::structs::timer::Timer;
+
+/// Path of main config and the timer directory, they are set once during startup
+static PATHS: RwLock<Option<(String, String)>> = RwLock::new(None);
+
+/// Set path of main config and the timer directory, it is called once during startup
+pub fn init(config_path: &str, timer_dir: &str) {
+    let mut paths = PATHS.write().unwrap();
+    *paths = Some((String::from(config_path), String::from(timer_dir)));
+}
+
+/// Read the main config again and reconcile every static timer with its file
+///
+/// Only `defaults.verbose`, `timer.log_dir` and Hermes settings are changed, other properties need restart.
+/// Timer directory is the one which was read during startup. If main config cannot be read, then nothing is changed.
+pub fn reload_config() -> Result<Vec<(String, Result<ReloadAction, String>)>, String> {
+    let (config_path, timer_dir) = match PATHS.read().unwrap().clone() {
+        Some(paths) => paths,
+        None => return Err(String::from("Chronos is not started yet")),
+    };
+
+    let config = match onlyati_config::read_config(&config_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Failed to read '{}' config: {}", config_path, e)),
+    };
+
+    let log_dir = match config.get("timer.log_dir") {
+        Some(dir) => dir,
+        None => return Err(String::from("Property 'timer.log_dir' is not specified in config")),
+    };
+    if crate::services::file::check_and_create_dir(Some(log_dir)) != 0 {
+        return Err(format!("Log directory '{}' cannot be used", log_dir));
+    }
+
+    if config.get("timer.all_dir") != Some(&timer_dir) {
+        eprintln!("Reload: Property 'timer.all_dir' is changed, it is applied after restart");
+    }
+
+    {
+        let mut verbose = crate::VERBOSE.write().unwrap();
+        *verbose = config.get("defaults.verbose").map(|x| x == "yes").unwrap_or(false);
+    }
+    crate::services::executor::set_log_dir(log_dir);
+    let hermes = crate::services::hermes_client::configure(&config);
+    println!("Reload: Config '{}' is read, log directory: {}, Hermes is {}", config_path, log_dir, if hermes { "enabled" } else { "disabled" });
+
+    let results = reconcile_dir(&timer_dir);
+    for (id, result) in &results {
+        log_result(id, result);
+    }
+
+    return Ok(results);
+}
+
+/// Reconcile the static timer with its `<id>.conf` file in the timer directory
+///
+/// New file is added, changed one is replaced and the timer of deleted one is purged. Timers whose file
+/// has not changed keep their schedule. Dynamic timers are not touched. Paused state is kept when the
+/// timer is replaced. If the file cannot be parsed, then the active timer is kept.
+pub fn reconcile_file(timer_dir: &str, id: &str) -> Result<ReloadAction, String> {
+    let path = format!("{}/{}.conf", timer_dir, id);
+
+    if !Path::new(&path).is_file() {
+        let removed = crate::services::registry::update_or_remove(id, |timer| {
+            if !timer.dynamic {
+                crate::services::state::forget_runs(id);
+            }
+            return (!timer.dynamic, timer.dynamic);
+        });
+        return match removed {
+            Some(true) => {
+                crate::services::state::flush();
+                crate::services::executor::forget(id);
+                Ok(ReloadAction::Removed)
+            }
+            _ => Ok(ReloadAction::Unchanged),
+        };
+    }
+
+    let mut config = crate::services::file::read_conf_file(&path)?;
+    config.insert(String::from("id"), String::from(id));
+
+    // Active timer: (dynamic, same config)
+    match crate::services::registry::update(id, |timer| (timer.dynamic, timer.config == config)) {
+        Some((true, _)) => return Err(format!("Timer '{}' is not loaded, because a dynamic timer has the same id", id)),
+        Some((false, true)) => return Ok(ReloadAction::Unchanged),
+        Some((false, false)) => {
+            let mut timer = Timer::from_config(config)?;
+            crate::services::state::restore_runs(&mut timer);
+            crate::services::registry::upsert(timer, |active_timer, timer| timer.paused = active_timer.paused)?;
+            return Ok(ReloadAction::Updated);
+        }
+        None => {
+            let mut timer = Timer::from_config(config)?;
+            crate::services::state::restore_runs(&mut timer);
+            crate::services::registry::insert(timer, |_| ())?;
+            return Ok(ReloadAction::Added);
+        }
+    }
+}
+
+/// Reconcile every file of the timer directory and every active static timer, it returns the result by timer id
+pub fn reconcile_dir(timer_dir: &str) -> Vec<(String, Result<ReloadAction, String>)> {
+    let mut ids: BTreeSet<String> = crate::services::registry::list()
+        .into_iter()
+        .filter(|x| !x.dynamic)
+        .map(|x| x.id)
+        .collect();
+
+    match std::fs::read_dir(timer_dir) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if let Some(id) = conf_id(&name) {
+                    ids.insert(id);
+                }
+            }
+        }
+        Err(e) => {
+            // Without listing, every static timer would be purged
+            eprintln!("Failed to read timer directory '{}': {}", timer_dir, e);
+            return Vec::new();
+        }
+    }
+
+    return ids.into_iter()
+        .map(|id| {
+            let result = reconcile_file(timer_dir, &id);
+            (id, result)
+        })
+        .collect();
+}
+
+/// Log the result of reconciliation, unchanged timers are only logged in verbose mode
+pub fn log_result(id: &str, result: &Result<ReloadAction, String>) {
+    match result {
+        Ok(ReloadAction::Unchanged) => verbose_println!("Reload: {}: Timer is unchanged", id),
+        Ok(action) => println!("Reload: {}: Timer is {}", id, action),
+        Err(e) => eprintln!("Reload: {}: Failed to reload timer: {}", id, e),
+    }
+}
+
+/// Summary of reconciliation, e.g. `2 added, 1 updated, 0 removed, 1 failed`
+pub fn summary(results: &[(String, Result<ReloadAction, String>)]) -> String {
+    let count = |action: ReloadAction| results.iter().filter(|(_, result)| result.as_ref() == Ok(&action)).count();
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+
+    return format!("{} added, {} updated, {} removed, {} failed", count(ReloadAction::Added), count(ReloadAction::Updated), count(ReloadAction::Removed), failed);
+}
+
+/// Timer id of file name, None if it is not a timer file
+pub fn conf_id(name: &str) -> Option<String> {
+    return match name.strip_suffix(".conf") {
+        Some(id) if !id.is_empty() => Some(String::from(id)),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_conf_files_are_timers() {
+        assert_eq!(conf_id("backup.conf"), Some(String::from("backup")));
+        assert_eq!(conf_id("db.backup.conf"), Some(String::from("db.backup")));
+        assert_eq!(conf_id("backup.conf~"), None);
+        assert_eq!(conf_id(".conf"), None);
+    }
+
+    #[test]
+    fn summary_counts_actions() {
+        let results = vec![
+            (String::from("a"), Ok(ReloadAction::Added)),
+            (String::from("b"), Ok(ReloadAction::Unchanged)),
+            (String::from("c"), Ok(ReloadAction::Removed)),
+            (String::from("d"), Err(String::from("parse error"))),
+            (String::from("e"), Ok(ReloadAction::Added)),
+        ];
+        assert_eq!(summary(&results), "2 added, 0 updated, 1 removed, 1 failed");
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Shutdown has been requested, new runs are not started anymore
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// How long active runs can finish if `shutdown.timeout` is not specified
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time which is given to Hermes client to send the pending statuses, even if the timeout has been passed
+const MIN_FLUSH_TIME: Duration = Duration::from_secs(5);
+
+/// How often shutdown checks that runs have ended
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Request shutdown and wake up the scheduler, so the main loop can stop Chronos
+pub fn request() {
+    if REQUESTED.swap(true, Ordering::SeqCst) {
+        println!("Shutdown: Chronos is already stopping");
+        return;
+    }
+
+    crate::services::timing::wake_up();
+}
+
+/// Check that shutdown has been requested
+pub fn requested() -> bool {
+    return REQUESTED.load(Ordering::SeqCst);
+}
+
+/// Stop Chronos gracefully, it is called by the main loop after the shutdown has been requested
+///
+/// Active runs can finish within the timeout, after that their process groups are terminated. Then the
+/// pending statuses are sent to Hermes and the state of dynamic timers is saved.
+pub fn shut_down(timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+
+    let active = crate::services::executor::active_runs();
+    if active > 0 {
+        println!("Shutdown: Wait at most {} seconds for {} active run(s)", timeout.as_secs(), active);
+    }
+
+    if !wait_until(deadline, || crate::services::executor::active_runs() == 0) {
+        let stopped = crate::services::executor::stop_all();
+        println!("Shutdown: {} run(s) are still active, their process groups are terminated", stopped);
+
+        let kill_deadline = Instant::now() + crate::structs::timer::KILL_GRACE_PERIOD + Duration::from_secs(1);
+        if !wait_until(kill_deadline, || crate::services::executor::active_runs() == 0) {
+            eprintln!("Shutdown: {} run(s) have not ended", crate::services::executor::active_runs());
+        }
+    }
+
+    let flush_deadline = deadline.max(Instant::now() + MIN_FLUSH_TIME);
+    if !wait_until(flush_deadline, || crate::services::hermes_client::pending() == 0) {
+        eprintln!("Shutdown: {} status(es) could not be sent to Hermes", crate::services::hermes_client::pending());
+    }
+
+    crate::services::registry::with_all(crate::services::state::compact);
+    crate::services::state::flush();
+    println!("Shutdown: Chronos is stopped");
+}
+
+/// Wait until the condition is true, it returns false if the deadline has passed before
+fn wait_until<F: Fn() -> bool>(deadline: Instant, condition: F) -> bool {
+    loop {
+        if condition() {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,42 @@
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Handle signals of Chronos, this must be run from a tokio runtime:
+/// - SIGHUP: reload the config and the timers
+/// - SIGTERM, SIGINT: stop Chronos gracefully
+pub async fn handle_signals() {
+    let signals = (signal(SignalKind::hangup()), signal(SignalKind::terminate()), signal(SignalKind::interrupt()));
+    let (mut hangup, mut terminate, mut interrupt) = match signals {
+        (Ok(hangup), Ok(terminate), Ok(interrupt)) => (hangup, terminate, interrupt),
+        _ => {
+            eprintln!("Failed to set signal handlers");
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {
+                println!("SIGHUP is received, reload config");
+                if let Err(e) = tokio::task::spawn_blocking(reload).await {
+                    eprintln!("Reload has failed: {}", e);
+                }
+            }
+            _ = terminate.recv() => {
+                println!("SIGTERM is received, stop Chronos");
+                crate::services::shutdown::request();
+            }
+            _ = interrupt.recv() => {
+                println!("SIGINT is received, stop Chronos");
+                crate::services::shutdown::request();
+            }
+        }
+    }
+}
+
+/// Reload config and log the summary
+fn reload() {
+    match crate::services::reload::reload_config() {
+        Ok(results) => println!("Reload: {}", crate::services::reload::summary(&results)),
+        Err(e) => eprintln!("Reload: {}", e),
+    }
+}
@@ -0,0 +1,493 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+
+use crate::structs::timer::Timer;
+use crate::enums::timer_types::TimerType;
+
+/// Directory where state of Chronos is stored, if it is not set, then nothing is persisted
+static STATE_DIR: RwLock<Option<String>> = RwLock::new(None);
+
+/// Name of journal file in the state directory which records the dynamic timers
+const JOURNAL_FILE: &str = "dynamic.journal";
+
+/// Property where the due time of oneshot timers is saved in the journal
+const NEXT_HIT_KEY: &str = "_next_hit";
+
+/// Property where the paused state is saved in the journal, only for paused timers
+const PAUSED_KEY: &str = "_paused";
+
+/// Last successful run of timers, seconds since UNIX_EPOCH, they are recorded in `last_run` file of the state directory
+static LAST_RUNS: Mutex<NumberFile> = Mutex::new(NumberFile::new("last_run"));
+
+/// Number of runs of timers with run limit, both static and dynamic ones, they are recorded in `runs` file
+/// of the state directory
+static RUNS: Mutex<NumberFile> = Mutex::new(NumberFile::new("runs"));
+
+/// Value of a removed number in a number file
+const REMOVED: &str = "-";
+
+/// Journal is compacted after this many lines have been appended to it, number files after this many lines
+/// more than their timers
+const COMPACT_AFTER: usize = 1000;
+
+/// Lines which have been appended to the journal since it was compacted
+static JOURNAL_LINES: AtomicUsize = AtomicUsize::new(0);
+
+/// Changes which are not written into the state directory yet. They are queued while the registry is locked, so
+/// they keep the order of timer changes, and `flush` writes them after the lock is released.
+static PENDING: Mutex<Vec<Change>> = Mutex::new(Vec::new());
+
+/// It is held while the pending changes are written, so they are written in the same order as they were queued
+static WRITER: Mutex<()> = Mutex::new(());
+
+/// A change of the state directory:
+/// - Journal: line is appended to the journal
+/// - Compact: journal is replaced with the content
+/// - Number: line is appended to a number file
+enum Change {
+    Journal(String),
+    Compact(String),
+    Number(&'static Mutex<NumberFile>, String),
+}
+
+/// Set the state directory and load the stored last runs and run counts, it is called once during startup
+pub fn init(state_dir: Option<&String>) {
+    {
+        let mut dir = STATE_DIR.write().unwrap();
+        *dir = state_dir.cloned();
+    }
+
+    LAST_RUNS.lock().unwrap().load();
+    RUNS.lock().unwrap().load();
+}
+
+/// Get when the timer has run successfully last time
+pub fn last_run(id: &str) -> Option<u64> {
+    return LAST_RUNS.lock().unwrap().get(id);
+}
+
+/// Record when the timer has run successfully, it is written by `flush`
+pub fn record_last_run(id: &str, time: u64) {
+    let mut last_runs = LAST_RUNS.lock().unwrap();
+    if let Some(line) = last_runs.set(id, time) {
+        queue(Change::Number(&LAST_RUNS, line));
+    }
+}
+
+/// Set the number of runs of timer from the state directory, if the timer has run limit and its runs are recorded
+pub fn restore_runs(timer: &mut Timer) {
+    if timer.max_runs.is_none() {
+        return;
+    }
+
+    if let Some(runs) = RUNS.lock().unwrap().get(&timer.id) {
+        timer.runs = runs;
+    }
+}
+
+/// Record the number of runs of timer, if it has run limit, it is written by `flush`
+pub fn record_runs(timer: &Timer) {
+    if timer.max_runs.is_none() {
+        return;
+    }
+
+    let mut runs = RUNS.lock().unwrap();
+    if let Some(line) = runs.set(&timer.id, timer.runs) {
+        queue(Change::Number(&RUNS, line));
+    }
+}
+
+/// Forget the number of runs of a purged timer, so a new timer with the same id starts from zero, it is written by `flush`
+pub fn forget_runs(id: &str) {
+    let mut runs = RUNS.lock().unwrap();
+    if let Some(line) = runs.remove(id) {
+        queue(Change::Number(&RUNS, line));
+    }
+}
+
+/// Write the pending changes into the state directory
+///
+/// It has to be called after the registry lock is released, because it waits for the disk.
+pub fn flush() {
+    let _writer = WRITER.lock().unwrap();
+    let changes = std::mem::take(&mut *PENDING.lock().unwrap());
+
+    for change in changes {
+        match change {
+            Change::Journal(line) => append_journal(&line),
+            Change::Compact(content) => write_journal(&content),
+            Change::Number(file, line) => file.lock().unwrap().write(&line),
+        }
+    }
+}
+
+/// Queue a change, it is dropped if the state directory is not set
+fn queue(change: Change) {
+    if STATE_DIR.read().unwrap().is_none() {
+        return;
+    }
+    PENDING.lock().unwrap().push(change);
+}
+
+/// A number of every timer which is recorded in a file of the state directory, e.g. the last successful run.
+/// Changes are appended to the file as `<id> <number>` lines, the later line wins, and the file is rewritten
+/// when it has much more lines than timers, so a change does not need to write every timer.
+/// - name: name of file in the state directory
+/// - numbers: current numbers, key is the timer id
+/// - appended: how many lines have been appended since the file was rewritten
+struct NumberFile {
+    name: &'static str,
+    numbers: BTreeMap<String, u64>,
+    appended: usize,
+}
+
+impl NumberFile {
+    const fn new(name: &'static str) -> Self {
+        return NumberFile {
+            name,
+            numbers: BTreeMap::new(),
+            appended: 0,
+        };
+    }
+
+    /// Read the numbers from the file, lines which are overridden by later ones count as appended
+    fn load(&mut self) {
+        self.numbers.clear();
+        self.appended = 0;
+
+        let path = match state_file(self.name) {
+            Some(p) => p,
+            None => return,
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    eprintln!("Failed to read '{}': {}", path, e);
+                }
+                return;
+            }
+        };
+
+        let mut lines = 0;
+        for line in content.lines() {
+            if let Some((id, number)) = line.split_once('\t') {
+                lines += 1;
+                if number == REMOVED {
+                    self.numbers.remove(&unescape(id));
+                    continue;
+                }
+                match number.parse::<u64>() {
+                    Ok(number) => {
+                        self.numbers.insert(unescape(id), number);
+                    }
+                    Err(_) => eprintln!("Invalid line in '{}': {}", path, line),
+                }
+            }
+        }
+        self.appended = lines - self.numbers.len();
+    }
+
+    /// Get number of timer
+    fn get(&self, id: &str) -> Option<u64> {
+        return self.numbers.get(id).copied();
+    }
+
+    /// Change number of timer, it returns the line which records the change, None if the number is the same
+    fn set(&mut self, id: &str, number: u64) -> Option<String> {
+        if self.numbers.insert(String::from(id), number) == Some(number) {
+            return None;
+        }
+        return Some(format!("{}\t{}", escape(id), number));
+    }
+
+    /// Remove number of timer, it returns the line which records the change, None if timer has no number
+    fn remove(&mut self, id: &str) -> Option<String> {
+        self.numbers.remove(id)?;
+        return Some(format!("{}\t{}", escape(id), REMOVED));
+    }
+
+    /// Append a line of change to the file, or rewrite the file if it has grown too much
+    ///
+    /// Rewritten file contains the current numbers, so the changes which are not written yet are also included.
+    fn write(&mut self, line: &str) {
+        let path = match state_file(self.name) {
+            Some(p) => p,
+            None => return,
+        };
+
+        // Rewrite costs as much as the appended lines, so it does not make changes slower on average
+        if self.appended >= COMPACT_AFTER + self.numbers.len() {
+            let mut content = String::new();
+            for (id, number) in self.numbers.iter() {
+                content += &format!("{}\t{}\n", escape(id), number);
+            }
+
+            match write_file(&path, &content) {
+                Ok(_) => self.appended = 0,
+                Err(e) => eprintln!("Failed to write '{}': {}", path, e),
+            }
+            return;
+        }
+
+        match append_line(&path, line) {
+            Ok(_) => self.appended += 1,
+            Err(e) => eprintln!("Failed to write '{}': {}", path, e),
+        }
+    }
+}
+
+/// Path of file in the state directory, None if state directory is not set
+pub fn state_file(name: &str) -> Option<String> {
+    let dir = STATE_DIR.read().unwrap();
+    return dir.as_ref().map(|dir| format!("{}/{}", dir, name));
+}
+
+/// Record that a dynamic timer has been created, it is written by `flush`
+pub fn record_create(timer: &Timer) {
+    queue(Change::Journal(format!("create\t{}", encode_timer(timer))));
+}
+
+/// Record that a dynamic timer has been changed (e.g. its paused state), it replaces the previous record during replay
+pub fn record_update(timer: &Timer) {
+    record_create(timer);
+}
+
+/// Record that a dynamic timer has been purged, it is written by `flush`
+pub fn record_purge(id: &str) {
+    queue(Change::Journal(format!("purge\t{}", escape(id))));
+    forget_runs(id);
+}
+
+/// Check that many lines have been appended to the journal since it was compacted
+pub fn compaction_due() -> bool {
+    return JOURNAL_LINES.load(Ordering::SeqCst) >= COMPACT_AFTER;
+}
+
+/// Read the journal and build the list of dynamic timers which were alive when Chronos stopped
+///
+/// Oneshot timers keep their original due time, other timers are scheduled from now.
+pub fn replay() -> Vec<Timer> {
+    let path = match state_file(JOURNAL_FILE) {
+        Some(p) => p,
+        None => return Vec::new(),
+    };
+
+    let file = match std::fs::File::open(&path) {
+        Ok(f) => f,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("Failed to open journal '{}': {}", path, e);
+            }
+            return Vec::new();
+        }
+    };
+
+    let mut configs: Vec<HashMap<String, String>> = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => {
+                eprintln!("Failed to read journal '{}': {}", path, e);
+                break;
+            }
+        };
+
+        let mut fields = line.split('\t');
+        match fields.next() {
+            Some("create") => {
+                let mut config: HashMap<String, String> = HashMap::new();
+                for field in fields {
+                    if let Some((key, value)) = field.split_once('=') {
+                        config.insert(unescape(key), unescape(value));
+                    }
+                }
+                configs.retain(|x| x.get("id") != config.get("id"));
+                configs.push(config);
+            }
+            Some("purge") => {
+                let id = unescape(fields.next().unwrap_or(""));
+                configs.retain(|x| x.get("id") != Some(&id));
+            }
+            Some("") | None => (),
+            Some(op) => eprintln!("Unknown operation in journal '{}': {}", path, op),
+        }
+    }
+
+    let mut timers: Vec<Timer> = Vec::new();
+    for mut config in configs {
+        let next_hit = config.remove(NEXT_HIT_KEY).and_then(|x| x.parse::<u64>().ok());
+        let paused = config.remove(PAUSED_KEY).is_some();
+
+        let mut timer = match Timer::from_config(config) {
+            Ok(timer) => timer,
+            Err(e) => {
+                eprintln!("Failed to restore dynamic timer from journal: {}", e);
+                continue;
+            }
+        };
+        timer.dynamic = true;
+        timer.paused = paused;
+        restore_runs(&mut timer);
+
+        if timer.r#type == TimerType::OneShot {
+            if let Some(next_hit) = next_hit {
+                timer.next_hit = next_hit;
+            }
+        }
+
+        timers.push(timer);
+    }
+
+    return timers;
+}
+
+/// Rewrite the journal, so it only contains the currently active dynamic timers, it is written by `flush`
+///
+/// Timers must not be changed meanwhile, else their records can be lost, see `registry::with_all`.
+pub fn compact(timers: &[Timer]) {
+    let mut content = String::new();
+    for timer in timers.iter().filter(|x| x.dynamic) {
+        content += &format!("create\t{}\n", encode_timer(timer));
+    }
+
+    queue(Change::Compact(content));
+}
+
+/// Replace the journal with the content
+fn write_journal(content: &str) {
+    let path = match state_file(JOURNAL_FILE) {
+        Some(p) => p,
+        None => return,
+    };
+
+    match write_file(&path, content) {
+        Ok(_) => JOURNAL_LINES.store(0, Ordering::SeqCst),
+        Err(e) => eprintln!("Failed to compact journal '{}': {}", path, e),
+    }
+}
+
+/// Write content into a temporary file, then rename it, so the file is never half written
+fn write_file(path: &str, content: &str) -> std::io::Result<()> {
+    let temp_path = format!("{}.tmp", path);
+
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+
+    return std::fs::rename(&temp_path, path);
+}
+
+/// Append a line to the journal and flush it to the disk
+fn append_journal(line: &str) {
+    let path = match state_file(JOURNAL_FILE) {
+        Some(p) => p,
+        None => return,
+    };
+
+    match append_line(&path, line) {
+        Ok(_) => {
+            JOURNAL_LINES.fetch_add(1, Ordering::SeqCst);
+        }
+        Err(e) => eprintln!("Failed to write journal '{}': {}", path, e),
+    }
+}
+
+/// Append a line to a file and flush it to the disk
+fn append_line(path: &str, line: &str) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)?;
+    return file.sync_data();
+}
+
+/// Convert timer to tab separated `key=value` pairs
+fn encode_timer(timer: &Timer) -> String {
+    let mut fields: Vec<String> = Vec::new();
+    for (key, value) in &timer.config {
+        fields.push(format!("{}={}", escape(key), escape(value)));
+    }
+    fields.sort();
+
+    if timer.r#type == TimerType::OneShot {
+        fields.push(format!("{}={}", NEXT_HIT_KEY, timer.next_hit));
+    }
+
+    if timer.paused {
+        fields.push(format!("{}=yes", PAUSED_KEY));
+    }
+
+    return fields.join("\t");
+}
+
+/// Escape characters which have special meaning in the journal
+fn escape(text: &str) -> String {
+    return text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('=', "\\e");
+}
+
+/// Revert `escape` function
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('e') => result.push('='),
+            Some(other) => result.push(other),
+            None => (),
+        }
+    }
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn number_file_keeps_the_latest_changes() {
+        let dir = std::env::temp_dir().join(format!("chronos-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        *STATE_DIR.write().unwrap() = Some(dir.to_string_lossy().to_string());
+
+        let mut file = NumberFile::new("numbers");
+        let mut changes: Vec<String> = Vec::new();
+        for n in 0..COMPACT_AFTER as u64 + 10 {
+            changes.extend(file.set("a", n));
+        }
+        changes.extend(file.set("b\tc", 5));
+        changes.extend(file.set("d", 6));
+        changes.extend(file.remove("d"));
+        assert!(file.set("b\tc", 5).is_none());
+        for line in changes {
+            file.write(&line);
+        }
+
+        let mut loaded = NumberFile::new("numbers");
+        loaded.load();
+        assert_eq!(loaded.numbers, file.numbers);
+        assert_eq!(loaded.get("a"), Some(COMPACT_AFTER as u64 + 9));
+        assert_eq!(loaded.get("b\tc"), Some(5));
+        assert_eq!(loaded.get("d"), None);
+
+        // File has been rewritten once, so it does not have a line for every change
+        let lines = std::fs::read_to_string(dir.join("numbers")).unwrap().lines().count();
+        assert!(lines < 20, "file has {} lines", lines);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,14 +1,82 @@
-use std::sync::mpsc::Sender;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
 
-pub async fn start_trigger_timer(sender: Sender<u64>) {
-    let interval = tokio::time::Duration::from_secs(1);
+use crate::structs::timer::Timer;
 
-    loop {
-        match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
-            Ok(v) => sender.send(v.as_secs()).unwrap(),
-            Err(e) => panic!("Could not get the second since UNIX_EPOCH: {}", e),
-        }
+/// Deadlines of timers (time and timer id), the earliest is on the top. Entries are not removed when a timer
+/// changes, instead a new entry is added and the outdated one is skipped when it is due.
+static DEADLINES: Mutex<BinaryHeap<Reverse<(u64, String)>>> = Mutex::new(BinaryHeap::new());
+
+/// Wake up the scheduler when a new deadline is added
+static WAKE_UP: Condvar = Condvar::new();
+
+/// Longest sleep of scheduler. Sleep follows the monotonic clock, so the wall clock is checked regularly to
+/// notice when it has been changed or the machine has been suspended.
+const MAX_SLEEP: Duration = Duration::from_secs(5);
+
+/// Add the deadline of timer to the scheduler and wake it up, so it sleeps until the right time
+///
+/// It has to be called after anything is changed that affects the deadline of timer, e.g. it is created or resumed.
+pub fn schedule(timer: &Timer) {
+    let deadline = timer.deadline();
+    if deadline == u64::MAX {
+        return;
+    }
+
+    let mut deadlines = DEADLINES.lock().unwrap();
+    deadlines.push(Reverse((deadline, timer.id.clone())));
+    WAKE_UP.notify_one();
+}
+
+/// Wake up the scheduler without a new deadline, e.g. when shutdown is requested
+pub fn wake_up() {
+    // Lock ensures that the scheduler is either waiting or has not checked the shutdown yet
+    let _deadlines = DEADLINES.lock().unwrap();
+    WAKE_UP.notify_one();
+}
+
+/// Sleep until the earliest deadline, then return the current time (seconds since UNIX_EPOCH) and the due entries
+///
+/// It returns earlier, maybe without due entries, when a new deadline is added, shutdown is requested or
+/// `MAX_SLEEP` has passed.
+pub fn wait_due() -> (u64, Vec<(u64, String)>) {
+    let mut deadlines = DEADLINES.lock().unwrap();
 
-        tokio::time::sleep(interval).await;
+    let now = now();
+    let sleep = match deadlines.peek() {
+        Some(Reverse((deadline, _))) => Duration::from_secs(*deadline).saturating_sub(now),
+        None => MAX_SLEEP,
+    };
+
+    if !sleep.is_zero() && !crate::services::shutdown::requested() {
+        deadlines = WAKE_UP.wait_timeout(deadlines, sleep.min(MAX_SLEEP)).unwrap().0;
+    }
+
+    let secs = now_secs();
+    let mut due: Vec<(u64, String)> = Vec::new();
+    while let Some(Reverse((deadline, _))) = deadlines.peek() {
+        if *deadline > secs {
+            break;
+        }
+        if let Some(Reverse(entry)) = deadlines.pop() {
+            due.push(entry);
+        }
     }
-}
\ No newline at end of file
+
+    return (secs, due);
+}
+
+/// Current time since UNIX_EPOCH
+fn now() -> Duration {
+    return match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(v) => v,
+        Err(e) => panic!("Could not get the second since UNIX_EPOCH: {}", e),
+    };
+}
+
+/// Current time, seconds since UNIX_EPOCH
+fn now_secs() -> u64 {
+    return now().as_secs();
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeSet;
+use std::ffi::CString;
+
+use crate::services::reload::{conf_id, log_result, reconcile_dir, reconcile_file};
+
+/// Events of timer directory which are watched: created (e.g. links), written, moved in or out and deleted files.
+/// A new file which is written causes both create and write events, they are reconciled once due to the quiet period.
+const WATCH_MASK: u32 = libc::IN_CREATE | libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_MOVED_FROM | libc::IN_DELETE;
+
+/// After an event, wait this long (in milliseconds) for further events, so a file which is saved in more steps
+/// (e.g. editor renames the old file and writes a new one) is reconciled once
+const QUIET_PERIOD: i32 = 500;
+
+/// Size of fixed part of an inotify event, the file name follows it
+const EVENT_SIZE: usize = std::mem::size_of::<libc::inotify_event>();
+
+/// Start to watch timer directory, then reconcile static timers in a new thread when its files change
+pub fn start(timer_dir: &str) -> Result<(), String> {
+    let path = match CString::new(timer_dir) {
+        Ok(p) => p,
+        Err(_) => return Err(format!("Invalid timer directory: '{}'", timer_dir)),
+    };
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(format!("Failed to initialize inotify: {}", std::io::Error::last_os_error()));
+    }
+
+    if unsafe { libc::inotify_add_watch(fd, path.as_ptr(), WATCH_MASK) } < 0 {
+        let error = std::io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(format!("Failed to watch '{}': {}", timer_dir, error));
+    }
+
+    println!("Watch timer directory '{}'", timer_dir);
+    let timer_dir = String::from(timer_dir);
+    std::thread::spawn(move || {
+        watch(fd, &timer_dir);
+        unsafe { libc::close(fd) };
+    });
+
+    return Ok(());
+}
+
+/// Read events until the watch is removed (e.g. directory is deleted) and reconcile the changed timers
+fn watch(fd: libc::c_int, timer_dir: &str) {
+    let mut buffer = [0u8; 4096];
+
+    loop {
+        let mut ids: BTreeSet<String> = BTreeSet::new();
+        let mut overflow = false;
+
+        // Collect events until the directory is quiet
+        loop {
+            let size = unsafe { libc::read(fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+            if size < 0 {
+                let error = std::io::Error::last_os_error();
+                if error.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                eprintln!("Failed to read events of '{}', it is not watched anymore: {}", timer_dir, error);
+                return;
+            }
+
+            let mut offset = 0;
+            while offset + EVENT_SIZE <= size as usize {
+                let event = unsafe { std::ptr::read_unaligned(buffer.as_ptr().add(offset) as *const libc::inotify_event) };
+                let name = &buffer[offset + EVENT_SIZE..offset + EVENT_SIZE + event.len as usize];
+                offset += EVENT_SIZE + event.len as usize;
+
+                if event.mask & libc::IN_IGNORED != 0 {
+                    eprintln!("Timer directory '{}' is not watched anymore", timer_dir);
+                    return;
+                }
+
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    overflow = true;
+                    continue;
+                }
+
+                // Name is padded with null characters
+                let name = String::from_utf8_lossy(name).trim_end_matches('\0').to_string();
+                if let Some(id) = conf_id(&name) {
+                    verbose_println!("watch: {}: Event {:#x}", name, event.mask);
+                    ids.insert(id);
+                }
+            }
+
+            let mut poll_fd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut poll_fd, 1, QUIET_PERIOD) } <= 0 {
+                break;
+            }
+        }
+
+        // Some events are lost, so everything is checked
+        if overflow {
+            println!("Too many changes in '{}', every timer is reconciled", timer_dir);
+            for (id, result) in reconcile_dir(timer_dir) {
+                log_result(&id, &result);
+            }
+            continue;
+        }
+
+        for id in ids {
+            let result = reconcile_file(timer_dir, &id);
+            log_result(&id, &result);
+        }
+    }
+}
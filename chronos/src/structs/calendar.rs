@@ -0,0 +1,58 @@
+use chrono::NaiveDate;
+
+/// Calendar which lists excluded dates:
+/// - name: name of calendar, it is the file name without `.cal` extension
+/// - ranges: excluded date ranges, both start and end are included
+#[derive(Clone)]
+pub struct Calendar {
+    pub name: String,
+    ranges: Vec<(NaiveDate, NaiveDate)>,
+}
+
+impl Calendar {
+    /// Parse calendar file content
+    ///
+    /// Every line contains a date (`2026-12-25`) or a date range (`2026-12-24..2026-12-26`).
+    /// Empty lines and lines which start with `#` are ignored.
+    pub fn parse(name: &str, content: &str) -> Result<Calendar, String> {
+        let mut ranges: Vec<(NaiveDate, NaiveDate)> = Vec::new();
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (start, end) = match line.split_once("..") {
+                Some((start, end)) => (start.trim(), end.trim()),
+                None => (line, line),
+            };
+
+            let start = parse_date(start).map_err(|e| format!("Invalid line {} in calendar '{}': {}", index + 1, name, e))?;
+            let end = parse_date(end).map_err(|e| format!("Invalid line {} in calendar '{}': {}", index + 1, name, e))?;
+            if start > end {
+                return Err(format!("Invalid line {} in calendar '{}': range is reversed", index + 1, name));
+            }
+
+            ranges.push((start, end));
+        }
+
+        return Ok(Calendar {
+            name: String::from(name),
+            ranges,
+        });
+    }
+
+    /// Check that date is excluded by the calendar
+    pub fn contains(&self, date: &NaiveDate) -> bool {
+        return self.ranges.iter().any(|(start, end)| start <= date && date <= end);
+    }
+}
+
+/// Parse date in YYYY-MM-DD format
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    return match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(d) => Ok(d),
+        Err(e) => Err(format!("'{}' is not a valid YYYY-MM-DD date: {}", date, e)),
+    };
+}
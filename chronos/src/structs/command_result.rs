@@ -0,0 +1,12 @@
+use crate::enums::execution_status::ExecutionStatus;
+use crate::structs::command_output::CommandOutput;
+
+/// Result of a timer execution:
+/// - output: lines of stdout and stderr in time order
+/// - code: exit code of command, -999 if it could not be determined
+/// - status: final status of execution
+pub struct CommandResult {
+    pub output: Vec<CommandOutput>,
+    pub code: i32,
+    pub status: ExecutionStatus,
+}
@@ -0,0 +1,291 @@
+use chrono::{Datelike, NaiveDate, TimeZone};
+
+use crate::enums::dst_policy::DstPolicy;
+
+const MONTH_NAMES: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How many days can be checked when looking for the next match. It is a bit more than 8 years,
+/// so even a February 29 schedule can find its next hit.
+pub const MAX_SEARCH_DAYS: u32 = 366 * 8 + 2;
+
+/// Local time which does not exist due to DST change can be moved forward by the DST policy, but not more than
+/// this. Clock changes in the tz database are at most 2 hours, so earlier times cannot be shifted after the start.
+const MAX_DST_SHIFT_HOURS: i64 = 3;
+
+/// Parsed cron expression:
+/// - expression: original expression as it was specified
+/// - seconds, minutes, hours, days_of_month, months, days_of_week: which values match, index is the value itself
+/// - dom_restricted and dow_restricted: day fields do not start with `*`, in this case they are in OR relation like in crontab
+#[derive(Clone)]
+pub struct CronSchedule {
+    pub expression: String,
+    seconds: Vec<bool>,
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSchedule {
+    /// Parse cron expression
+    ///
+    /// Expression can have 5 fields (minute hour day-of-month month day-of-week) or 6 fields, in this case
+    /// the first one is the second. Every field can contain lists (`1,15`), ranges (`8-18`), steps (`*/15`, `10-50/10`)
+    /// and names for months (`jan`) and days of week (`mon`).
+    pub fn parse(expression: &str) -> Result<CronSchedule, String> {
+        let fields = expression.split_whitespace().collect::<Vec<&str>>();
+        let normalized = fields.join(" ");
+
+        let fields = match fields.len() {
+            5 => {
+                let mut with_seconds = vec!["0"];
+                with_seconds.extend(fields);
+                with_seconds
+            }
+            6 => fields,
+            _ => return Err(format!("Cron expression '{}' must have 5 or 6 fields", expression)),
+        };
+
+        let seconds = parse_field(fields[0], 0, 59, None)
+            .map_err(|e| format!("Invalid second field in '{}': {}", expression, e))?;
+        let minutes = parse_field(fields[1], 0, 59, None)
+            .map_err(|e| format!("Invalid minute field in '{}': {}", expression, e))?;
+        let hours = parse_field(fields[2], 0, 23, None)
+            .map_err(|e| format!("Invalid hour field in '{}': {}", expression, e))?;
+        let days_of_month = parse_field(fields[3], 1, 31, None)
+            .map_err(|e| format!("Invalid day of month field in '{}': {}", expression, e))?;
+        let months = parse_field(fields[4], 1, 12, Some(&MONTH_NAMES))
+            .map_err(|e| format!("Invalid month field in '{}': {}", expression, e))?;
+        let mut days_of_week = parse_field(fields[5], 0, 7, Some(&DAY_NAMES))
+            .map_err(|e| format!("Invalid day of week field in '{}': {}", expression, e))?;
+
+        // Both 0 and 7 mean Sunday
+        if days_of_week[7] {
+            days_of_week[0] = true;
+        }
+        days_of_week.truncate(7);
+
+        return Ok(CronSchedule {
+            expression: normalized,
+            seconds,
+            minutes,
+            hours,
+            days_of_month,
+            months,
+            days_of_week,
+            // Like in Vixie cron, a field starting with `*` (e.g. `*/2`) is not restricted
+            dom_restricted: !fields[3].starts_with('*'),
+            dow_restricted: !fields[5].starts_with('*'),
+        });
+    }
+
+    /// Check that schedule can run on a specific day
+    fn matches_date(&self, date: &NaiveDate) -> bool {
+        if !self.months[date.month() as usize] {
+            return false;
+        }
+
+        let dom = self.days_of_month[date.day() as usize];
+        let dow = self.days_of_week[date.weekday().num_days_from_sunday() as usize];
+
+        return match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        };
+    }
+
+    /// Calculate the first matching time which is later than `from`, seconds since UNIX_EPOCH
+    ///
+    /// Fields are matched with local time of the time zone, local time which does not exist due to
+    /// DST change is handled by the DST policy.
+    pub fn next_after<Tz: TimeZone>(&self, tz: &Tz, from: u64, dst_policy: DstPolicy) -> Option<u64> {
+        let start = tz.timestamp_opt(from as i64, 0).single()?;
+        let start_date = start.date_naive();
+
+        // Earlier times of today are not checked, except the ones which can be shifted after `from` by DST
+        let earliest = start.naive_local() - chrono::Duration::hours(MAX_DST_SHIFT_HOURS);
+
+        let mut date = start_date;
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_date(&date) {
+                for hour in 0..24 {
+                    if !self.hours[hour] || (date == start_date && date.and_hms_opt(hour as u32, 59, 59)? < earliest) {
+                        continue;
+                    }
+                    for minute in 0..60 {
+                        if !self.minutes[minute] || (date == start_date && date.and_hms_opt(hour as u32, minute as u32, 59)? < earliest) {
+                            continue;
+                        }
+                        for second in 0..60 {
+                            if !self.seconds[second] {
+                                continue;
+                            }
+
+                            let naive = match date.and_hms_opt(hour as u32, minute as u32, second as u32) {
+                                Some(n) => n,
+                                None => continue,
+                            };
+
+                            let hit = match dst_policy.resolve(tz, &naive) {
+                                Some(hit) => hit,
+                                None => continue,
+                            };
+
+                            if hit > from {
+                                return Some(hit);
+                            }
+                        }
+                    }
+                }
+            }
+
+            date = date.succ_opt()?;
+        }
+
+        return None;
+    }
+}
+
+/// Parse one field of cron expression, returned vector index is the value, true means it matches
+fn parse_field(field: &str, min: usize, max: usize, names: Option<&[&str]>) -> Result<Vec<bool>, String> {
+    let mut values = vec![false; max + 1];
+
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step = match step.parse::<usize>() {
+                    Ok(s) if s > 0 => s,
+                    _ => return Err(format!("step '{}' is not a positive number", step)),
+                };
+                (range, step)
+            }
+            None => (item, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        }
+        else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, max, names)?, parse_value(end, min, max, names)?)
+        }
+        else {
+            let start = parse_value(range, min, max, names)?;
+            // Like in crontab, `5/10` means from 5 until the end of the range
+            if item.contains('/') {
+                (start, max)
+            }
+            else {
+                (start, start)
+            }
+        };
+
+        if start > end {
+            return Err(format!("range '{}' is reversed", range));
+        }
+
+        for value in (start..=end).step_by(step) {
+            values[value] = true;
+        }
+    }
+
+    return Ok(values);
+}
+
+/// Parse a single value of a cron field, it can be number or name (if names are provided)
+fn parse_value(value: &str, min: usize, max: usize, names: Option<&[&str]>) -> Result<usize, String> {
+    if let Some(names) = names {
+        let lower = value.to_lowercase();
+        if let Some(index) = names.iter().position(|n| *n == lower) {
+            // Months are counted from 1, days of week from 0
+            return Ok(index + min);
+        }
+    }
+
+    let number = match value.parse::<usize>() {
+        Ok(n) => n,
+        Err(_) => return Err(format!("'{}' is not a valid value", value)),
+    };
+
+    if number < min || number > max {
+        return Err(format!("'{}' is out of range {}-{}", value, min, max));
+    }
+
+    return Ok(number);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds since UNIX_EPOCH of a UTC date and time
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> u64 {
+        return NaiveDate::from_ymd_opt(y, mo, d).unwrap().and_hms_opt(h, mi, s).unwrap().and_utc().timestamp() as u64;
+    }
+
+    fn next(expression: &str, from: u64) -> Option<u64> {
+        return CronSchedule::parse(expression).unwrap().next_after(&chrono::Utc, from, DstPolicy::Skip);
+    }
+
+    #[test]
+    fn steps_ranges_and_lists() {
+        // 2024-01-01 is Monday
+        let from = utc(2024, 1, 1, 10, 7, 0);
+        assert_eq!(next("*/15 * * * *", from), Some(utc(2024, 1, 1, 10, 15, 0)));
+        assert_eq!(next("10-50/20 * * * *", from), Some(utc(2024, 1, 1, 10, 10, 0)));
+        assert_eq!(next("5/30 * * * *", utc(2024, 1, 1, 10, 36, 0)), Some(utc(2024, 1, 1, 11, 5, 0)));
+        assert_eq!(next("0 8-9 * * *", from), Some(utc(2024, 1, 2, 8, 0, 0)));
+        assert_eq!(next("0 9,12,18 * * *", from), Some(utc(2024, 1, 1, 12, 0, 0)));
+        assert_eq!(next("0 0 1 mar *", from), Some(utc(2024, 3, 1, 0, 0, 0)));
+        assert_eq!(next("0 0 * * sat,sun", from), Some(utc(2024, 1, 6, 0, 0, 0)));
+        assert_eq!(next("0 0 * * mon-wed", utc(2024, 1, 3, 10, 0, 0)), Some(utc(2024, 1, 8, 0, 0, 0)));
+        assert_eq!(next("0 0 * * 7", from), Some(utc(2024, 1, 7, 0, 0, 0)));
+    }
+
+    #[test]
+    fn seconds_field() {
+        let from = utc(2024, 1, 1, 10, 7, 20);
+        assert_eq!(next("*/30 * * * * *", from), Some(utc(2024, 1, 1, 10, 7, 30)));
+        assert_eq!(next("15 0 12 * * *", from), Some(utc(2024, 1, 1, 12, 0, 15)));
+        // 5 fields mean second 0
+        assert_eq!(next("* * * * *", from), Some(utc(2024, 1, 1, 10, 8, 0)));
+    }
+
+    #[test]
+    fn restricted_day_fields_are_in_or_relation() {
+        let from = utc(2024, 1, 1, 10, 0, 0);
+        // 13th of month or Friday, first is Friday 5th
+        assert_eq!(next("0 0 13 * fri", from), Some(utc(2024, 1, 5, 0, 0, 0)));
+        // Only day of month is restricted, `*/2` for day of week is not
+        assert_eq!(next("0 0 13 * */2", from), Some(utc(2024, 1, 13, 0, 0, 0)));
+        // Only day of week is restricted, `*/2` for day of month is not
+        assert_eq!(next("0 0 */2 * fri", from), Some(utc(2024, 1, 5, 0, 0, 0)));
+    }
+
+    #[test]
+    fn time_shifted_by_dst_is_found_after_start() {
+        // Lord Howe Island moves the clock from 02:00 to 02:30 on 2026-10-04, so 02:15 is shifted to 02:45 (15:45 UTC)
+        let schedule = CronSchedule::parse("15 2 * * *").unwrap();
+        let from = utc(2026, 10, 3, 15, 35, 0);
+        assert_eq!(schedule.next_after(&chrono_tz::Australia::Lord_Howe, from, DstPolicy::Shift), Some(utc(2026, 10, 3, 15, 45, 0)));
+    }
+
+    #[test]
+    fn impossible_date_has_no_match() {
+        assert_eq!(next("0 0 31 feb *", utc(2024, 1, 1, 0, 0, 0)), None);
+        assert_eq!(next("0 0 29 feb *", utc(2024, 3, 1, 0, 0, 0)), Some(utc(2028, 2, 29, 0, 0, 0)));
+    }
+
+    #[test]
+    fn invalid_expressions_are_rejected() {
+        for expression in ["", "* * * *", "* * * * * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "* * * 13 *",
+                           "* * * * 8", "*/0 * * * *", "30-10 * * * *", "* * * foo *", "1,,2 * * * *"] {
+            assert!(CronSchedule::parse(expression).is_err(), "'{}' should be rejected", expression);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,84 @@
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+const ORDINALS: [&str; 5] = ["first", "second", "third", "fourth", "fifth"];
+
+/// One rule of `day_of_month` property:
+/// - Day: specific day of month, e.g. `15`
+/// - Last: last day of month
+/// - Nth: nth weekday of month, e.g. `first monday`
+/// - LastWeekday: last weekday of month, e.g. `last friday`
+#[derive(Clone)]
+enum DayRule {
+    Day(u32),
+    Last,
+    Nth(u32, Weekday),
+    LastWeekday(Weekday),
+}
+
+/// Parsed `day_of_month` property:
+/// - rules: day matches if any rule matches
+#[derive(Clone)]
+pub struct DayOfMonth {
+    rules: Vec<DayRule>,
+}
+
+impl DayOfMonth {
+    /// Parse comma separated list of rules, e.g. `1, 15, last` or `first monday, last friday`
+    pub fn parse(expression: &str) -> Result<DayOfMonth, String> {
+        let mut rules: Vec<DayRule> = Vec::new();
+
+        for item in expression.split(',').map(|x| x.trim().to_lowercase()).filter(|x| !x.is_empty()) {
+            let words = item.split_whitespace().collect::<Vec<&str>>();
+            let rule = match words.as_slice() {
+                ["last"] => DayRule::Last,
+                [day] => match day.parse::<u32>() {
+                    Ok(day) if (1..=31).contains(&day) => DayRule::Day(day),
+                    _ => return Err(format!("Invalid day of month '{}', it must be between 1 and 31 or last", item)),
+                },
+                [nth, weekday] => {
+                    let weekday = match weekday.parse::<Weekday>() {
+                        Ok(w) => w,
+                        Err(_) => return Err(format!("Invalid day of week in '{}'", item)),
+                    };
+                    if *nth == "last" {
+                        DayRule::LastWeekday(weekday)
+                    }
+                    else {
+                        match ORDINALS.iter().position(|x| x == nth) {
+                            Some(index) => DayRule::Nth(index as u32 + 1, weekday),
+                            None => return Err(format!("Invalid ordinal in '{}', it can be first, second, third, fourth, fifth or last", item)),
+                        }
+                    }
+                }
+                _ => return Err(format!("Invalid day of month rule: '{}'", item)),
+            };
+            rules.push(rule);
+        }
+
+        if rules.is_empty() {
+            return Err(String::from("Property 'day_of_month' is empty"));
+        }
+
+        return Ok(DayOfMonth {
+            rules,
+        });
+    }
+
+    /// Check that any rule matches with the date
+    pub fn matches(&self, date: &NaiveDate) -> bool {
+        return self.rules.iter().any(|rule| match rule {
+            DayRule::Day(day) => date.day() == *day,
+            DayRule::Last => !same_month(date, 1),
+            DayRule::Nth(nth, weekday) => date.weekday() == *weekday && (date.day() - 1) / 7 + 1 == *nth,
+            DayRule::LastWeekday(weekday) => date.weekday() == *weekday && !same_month(date, 7),
+        });
+    }
+}
+
+/// Check that the date which is `days` later is still in the same month
+fn same_month(date: &NaiveDate, days: i64) -> bool {
+    return match date.checked_add_signed(Duration::days(days)) {
+        Some(later) => later.month() == date.month(),
+        None => false,
+    };
+}
@@ -1,2 +1,7 @@
 pub mod timer;
-pub mod command_output;
\ No newline at end of file
+pub mod command_output;
+pub mod cron_schedule;
+pub mod run_as;
+pub mod command_result;
+pub mod calendar;
+pub mod day_of_month;
\ No newline at end of file
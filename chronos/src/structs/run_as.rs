@@ -0,0 +1,94 @@
+use std::ffi::{CStr, CString};
+
+/// Account information which is used to execute command of timer:
+/// - user: name of user
+/// - uid: user id
+/// - gid: primary group id, it is the group of user or the group specified in timer
+/// - home: home directory of user
+#[derive(Clone)]
+pub struct RunAs {
+    pub user: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+}
+
+impl RunAs {
+    /// Look up user (and group if specified) in the system account database
+    pub fn lookup(user: &str, group: Option<&str>) -> Result<RunAs, String> {
+        let c_user = match CString::new(user) {
+            Ok(u) => u,
+            Err(_) => return Err(format!("Invalid user name: {}", user)),
+        };
+
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+        let mut buffer = vec![0 as libc::c_char; 16384];
+
+        let rc = unsafe {
+            libc::getpwnam_r(c_user.as_ptr(), &mut pwd, buffer.as_mut_ptr(), buffer.len(), &mut result)
+        };
+        if rc != 0 || result.is_null() {
+            return Err(format!("User '{}' does not exist", user));
+        }
+
+        let home = unsafe { CStr::from_ptr(pwd.pw_dir) }.to_string_lossy().to_string();
+
+        let gid = match group {
+            Some(group) => lookup_group(group)?,
+            None => pwd.pw_gid,
+        };
+
+        return Ok(RunAs {
+            user: String::from(user),
+            uid: pwd.pw_uid,
+            gid,
+            home,
+        });
+    }
+
+    /// Collect every group of user, it is used as supplementary groups of the command
+    pub fn groups(&self) -> Vec<libc::gid_t> {
+        let c_user = match CString::new(self.user.clone()) {
+            Ok(u) => u,
+            Err(_) => return vec![self.gid],
+        };
+
+        let mut count: libc::c_int = 64;
+        loop {
+            let mut groups: Vec<libc::gid_t> = vec![0; count as usize];
+            let rc = unsafe {
+                libc::getgrouplist(c_user.as_ptr(), self.gid, groups.as_mut_ptr(), &mut count)
+            };
+            if rc >= 0 {
+                groups.truncate(count as usize);
+                return groups;
+            }
+            // Buffer was too small, `count` contains the required size
+            if count as usize <= groups.len() {
+                count = groups.len() as libc::c_int * 2;
+            }
+        }
+    }
+}
+
+/// Look up group id by group name
+fn lookup_group(group: &str) -> Result<u32, String> {
+    let c_group = match CString::new(group) {
+        Ok(g) => g,
+        Err(_) => return Err(format!("Invalid group name: {}", group)),
+    };
+
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    let mut buffer = vec![0 as libc::c_char; 16384];
+
+    let rc = unsafe {
+        libc::getgrnam_r(c_group.as_ptr(), &mut grp, buffer.as_mut_ptr(), buffer.len(), &mut result)
+    };
+    if rc != 0 || result.is_null() {
+        return Err(format!("Group '{}' does not exist", group));
+    }
+
+    return Ok(grp.gr_gid);
+}
@@ -1,13 +1,45 @@
 use std::collections::HashMap; 
 use std::process::{Command, Stdio};
 use std::io::BufReader;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 
-use chrono::{Datelike, NaiveTime, Timelike, Local};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Local, TimeZone};
+use chrono_tz::Tz;
+use rand::Rng;
 use tokio::time::Duration;
 
 use crate::enums::timer_types::TimerType;
 use crate::enums::command_output_type::CommandOutputType;
+use crate::enums::execution_status::ExecutionStatus;
+use crate::enums::overlap_policy::OverlapPolicy;
+use crate::enums::catchup_policy::CatchupPolicy;
+use crate::enums::dst_policy::DstPolicy;
+use crate::enums::dependency_condition::DependencyCondition;
+use crate::enums::retry_backoff::RetryBackoff;
 use crate::structs::command_output::CommandOutput;
+use crate::structs::cron_schedule::{CronSchedule, MAX_SEARCH_DAYS};
+use crate::structs::day_of_month::DayOfMonth;
+use crate::structs::run_as::RunAs;
+use crate::structs::command_result::CommandResult;
+use crate::structs::calendar::Calendar;
+use crate::services::duration::{parse_duration, SECONDS_PER_DAY};
+
+/// How long a timed out command can stop after SIGTERM before it gets SIGKILL
+pub const KILL_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Upper limit of missed runs which are counted, it prevents endless catch-up of frequent timers
+pub const MAX_MISSED_RUNS: u64 = 100;
+
+/// Delay before retry if `retry_delay` is not specified
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Exponential backoff does not wait longer than this between retries
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Upper limit of days which can be skipped one after another due to exclude calendars
+const MAX_EXCLUDED_DAYS: u32 = 366 * 2;
 
 /// Timer struct that store data about timer:
 /// - id: indentifier of timer, must be unique
@@ -16,6 +48,32 @@ use crate::structs::command_output::CommandOutput;
 /// - command: what command timer has to be executed
 /// - next_hit: when timer can run next time, seconds since UNIX_EPOCH
 /// - days: which day timer can run, 'X' mean run and '_' mean don't run
+/// - schedule: parsed cron expression, only used by `TimerType::Cron`
+/// - run_as: which user and group execute the command, if not set, then the daemon's user
+/// - timeout: maximum runtime of command, after that its process group is killed
+/// - overlap: what to do if timer should run while its previous run is still active
+/// - catchup: what to do with runs which were missed during downtime
+/// - dst_policy: how to handle time of day which does not exist due to DST change
+/// - timezone: time zone where schedule is calculated, if it is not set then local time zone of server is used
+/// - random_delay: every run is delayed with a random time within this window
+/// - jitter: interval of every timers is changed with a random time within plus-minus this window
+/// - stable_jitter: random offsets are derived from host name and timer id, so they are the same after restart
+/// - offset: random offset of the next hit, seconds, `next_hit` already contains it
+/// - exclude: calendars of dates when timer does not run
+/// - times: times of day when at timer runs, sorted, the first one is the same as `interval`
+/// - day_of_month: which days of month timer can run, it is checked besides `days`
+/// - not_before: timer does not run before this time, seconds since UNIX_EPOCH
+/// - not_after: timer does not run after this time and it is retired, seconds since UNIX_EPOCH
+/// - max_runs: timer is removed after this many runs
+/// - runs: how many times the timer has been triggered
+/// - after: id of timer whose finished run triggers this timer, only used by `TimerType::After`
+/// - on: which result of the preceding run triggers this timer
+/// - retries: how many times failed command is executed again
+/// - retry_delay: delay before the first retry
+/// - retry_backoff: how the delay changes between retries
+/// - concurrency_group: runs of timers in the same group are limited by `group.<name>.max_concurrent` config
+/// - paused: timer does not run until it is resumed
+/// - config: properties which the timer was created from
 #[derive(Clone)]
 pub struct Timer {
     pub id: String,
@@ -25,11 +83,38 @@ pub struct Timer {
     pub next_hit: u64,
     pub days: Vec<char>,
     pub dynamic: bool,
+    pub schedule: Option<CronSchedule>,
+    pub run_as: Option<RunAs>,
+    pub timeout: Option<Duration>,
+    pub overlap: OverlapPolicy,
+    pub catchup: CatchupPolicy,
+    pub dst_policy: DstPolicy,
+    pub timezone: Option<Tz>,
+    pub random_delay: Option<Duration>,
+    pub jitter: Option<Duration>,
+    pub stable_jitter: bool,
+    pub offset: i64,
+    pub exclude: Vec<Calendar>,
+    pub times: Vec<Duration>,
+    pub day_of_month: Option<DayOfMonth>,
+    pub not_before: Option<u64>,
+    pub not_after: Option<u64>,
+    pub max_runs: Option<u64>,
+    pub runs: u64,
+    pub after: Option<String>,
+    pub on: DependencyCondition,
+    pub retries: u64,
+    pub retry_delay: Duration,
+    pub retry_backoff: RetryBackoff,
+    pub concurrency_group: Option<String>,
+    pub paused: bool,
+    pub config: HashMap<String, String>,
 }
 
 impl Timer {
     /// Create new timer from specified informations
-    pub fn new(id: String, r#type: TimerType, interval: Duration, command: Vec<String>, days: Vec<char>, dynamic: bool) -> Self {
+    pub fn new(id: String, r#type: TimerType, interval: Duration, command: Vec<String>, days: Vec<char>, dynamic: bool, schedule: Option<CronSchedule>) -> Self {
+        let times = if r#type == TimerType::At { vec![interval] } else { Vec::new() };
         let mut timer = Timer {
             id,
             r#type,
@@ -38,6 +123,32 @@ impl Timer {
             next_hit: 0,
             days,
             dynamic,
+            schedule,
+            run_as: None,
+            timeout: None,
+            overlap: OverlapPolicy::Allow,
+            catchup: CatchupPolicy::None,
+            dst_policy: DstPolicy::Shift,
+            timezone: None,
+            random_delay: None,
+            jitter: None,
+            stable_jitter: false,
+            offset: 0,
+            exclude: Vec::new(),
+            times,
+            day_of_month: None,
+            not_before: None,
+            not_after: None,
+            max_runs: None,
+            runs: 0,
+            after: None,
+            on: DependencyCondition::Success,
+            retries: 0,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            retry_backoff: RetryBackoff::Fixed,
+            concurrency_group: None,
+            paused: false,
+            config: HashMap::new(),
         };
 
         timer.calculate_next_hit();
@@ -67,23 +178,111 @@ impl Timer {
                 else if r#type == "every" {
                     TimerType::Every
                 }
+                else if r#type == "cron" {
+                    TimerType::Cron
+                }
+                else if r#type == "after" {
+                    TimerType::After
+                }
                 else {
-                    return Err(String::from("Acceptable values for 'type' property: at, oneshot or every"));
+                    return Err(String::from("Acceptable values for 'type' property: at, oneshot, every, cron or after"));
                 }
             }
+            // Timer which depends on another one does not need type
+            None if config.contains_key("after") => TimerType::After,
             None => return Err(String::from("Property 'type' is not specified")),
         };
 
-        // Parse for interval
-        let interval = match config.get("interval") {
-            Some(interval) => {
-                let time = match NaiveTime::parse_from_str(interval, "%H:%M:%S") {
+        // Parse for preceding timer and the condition which triggers this timer
+        let after = match config.get("after") {
+            Some(after) => {
+                if r#type != TimerType::After {
+                    return Err(String::from("Property 'after' can be used only by after timers"));
+                }
+                if after == &id {
+                    return Err(String::from("Timer cannot run after itself"));
+                }
+                Some(after.clone())
+            }
+            None => {
+                if r#type == TimerType::After {
+                    return Err(String::from("Property 'after' is not specified"));
+                }
+                None
+            }
+        };
+
+        let on = match config.get("on") {
+            Some(on) => {
+                if on == "success" {
+                    DependencyCondition::Success
+                }
+                else if on == "failure" {
+                    DependencyCondition::Failure
+                }
+                else if on == "any" {
+                    DependencyCondition::Any
+                }
+                else {
+                    return Err(String::from("Acceptable values for 'on' property: success, failure or any"));
+                }
+            }
+            None => DependencyCondition::Success,
+        };
+
+        // Parse for cron expression, it replaces interval and days for cron timers
+        let schedule = if r#type == TimerType::Cron {
+            match config.get("schedule") {
+                Some(schedule) => Some(CronSchedule::parse(schedule)?),
+                None => return Err(String::from("Property 'schedule' is not specified")),
+            }
+        }
+        else {
+            None
+        };
+
+        // Parse for interval, at timers can have more times of day in `interval` or in `times` property
+        let mut times: Vec<Duration> = Vec::new();
+        let interval = if r#type == TimerType::Cron || r#type == TimerType::After {
+            Duration::from_secs(0)
+        }
+        else if r#type == TimerType::At {
+            let list = match (config.get("times"), config.get("interval")) {
+                (Some(list), _) => list,
+                (None, Some(list)) => list,
+                (None, None) => return Err(String::from("Property 'interval' or 'times' is not specified")),
+            };
+
+            for time in list.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+                let time = match parse_duration(time) {
+                    Ok(t) => t,
+                    Err(e) => return Err(format!("Failed to parse time of day: {}", e)),
+                };
+                if time.as_secs() >= SECONDS_PER_DAY {
+                    return Err(String::from("Time of day of at timer must be between 00:00:00 and 23:59:59"));
+                }
+                times.push(time);
+            }
+
+            if times.is_empty() {
+                return Err(String::from("At timer must have at least one time of day"));
+            }
+            times.sort();
+            times.dedup();
+            times[0]
+        }
+        else {
+            if config.contains_key("times") {
+                return Err(String::from("Property 'times' can be used only by at timers"));
+            }
+
+            match config.get("interval") {
+                Some(interval) => match parse_duration(interval) {
                     Ok(i) => i,
                     Err(e) => return Err(format!("Failed to parse interval: {}", e)),
-                };
-                tokio::time::Duration::from_secs(time.num_seconds_from_midnight().into())
+                },
+                None => return Err(String::from("Property 'interval' is not specified")),
             }
-            None => return Err(String::from("Property 'interval' is not specified")),
         };
 
         // Parse for command
@@ -110,132 +309,590 @@ impl Timer {
             None => vec!['X', 'X', 'X', 'X', 'X', 'X', 'X']
         };
 
-        let timer = Timer::new(id, r#type, interval, command, days, false);
+        // Parse for days of month, cron timers have their own field in schedule
+        let day_of_month = match config.get("day_of_month") {
+            Some(day_of_month) => {
+                if r#type == TimerType::Cron {
+                    return Err(String::from("Property 'day_of_month' cannot be used by cron timers, use the day of month field of 'schedule'"));
+                }
+                Some(DayOfMonth::parse(day_of_month)?)
+            }
+            None => None,
+        };
+
+        // Parse for user and group who execute the command
+        let run_as = match (config.get("user"), config.get("group")) {
+            (Some(user), group) => Some(RunAs::lookup(user, group.map(|x| x.as_str()))?),
+            (None, Some(_)) => return Err(String::from("Property 'group' can be used only with 'user' property")),
+            (None, None) => None,
+        };
+
+        // Parse for timeout
+        let timeout = match config.get("timeout") {
+            Some(timeout) => {
+                let timeout = match parse_duration(timeout) {
+                    Ok(t) => t,
+                    Err(e) => return Err(format!("Failed to parse timeout: {}", e)),
+                };
+                if timeout.is_zero() {
+                    return Err(String::from("Property 'timeout' must be greater than 00:00:00"));
+                }
+                Some(timeout)
+            }
+            None => None,
+        };
+
+        // Parse for overlap policy
+        let overlap = match config.get("overlap") {
+            Some(overlap) => {
+                if overlap == "allow" {
+                    OverlapPolicy::Allow
+                }
+                else if overlap == "skip" {
+                    OverlapPolicy::Skip
+                }
+                else if overlap == "queue" {
+                    OverlapPolicy::Queue
+                }
+                else if overlap == "replace" {
+                    OverlapPolicy::Replace
+                }
+                else {
+                    return Err(String::from("Acceptable values for 'overlap' property: allow, skip, queue or replace"));
+                }
+            }
+            None => OverlapPolicy::Allow,
+        };
+
+        // Parse for catch-up policy
+        let catchup = match config.get("catchup") {
+            Some(catchup) => {
+                if catchup == "none" {
+                    CatchupPolicy::None
+                }
+                else if catchup == "once" {
+                    CatchupPolicy::Once
+                }
+                else if catchup == "all" {
+                    CatchupPolicy::All
+                }
+                else {
+                    return Err(String::from("Acceptable values for 'catchup' property: none, once or all"));
+                }
+            }
+            None => CatchupPolicy::None,
+        };
+
+        // Parse for DST policy
+        let dst_policy = match config.get("dst_policy") {
+            Some(dst_policy) => {
+                if dst_policy == "skip" {
+                    DstPolicy::Skip
+                }
+                else if dst_policy == "shift" {
+                    DstPolicy::Shift
+                }
+                else {
+                    return Err(String::from("Acceptable values for 'dst_policy' property: skip or shift"));
+                }
+            }
+            None => DstPolicy::Shift,
+        };
+
+        // Parse for time zone, e.g. Europe/Budapest
+        let timezone = match config.get("timezone") {
+            Some(timezone) => match timezone.parse::<Tz>() {
+                Ok(tz) => Some(tz),
+                Err(_) => return Err(format!("Unknown time zone in 'timezone' property: {}", timezone)),
+            },
+            None => None,
+        };
+
+        // Parse for random delay, it can be used by any timer
+        let random_delay = match config.get("random_delay") {
+            Some(random_delay) => match parse_duration(random_delay) {
+                Ok(d) => Some(d),
+                Err(e) => return Err(format!("Failed to parse random_delay: {}", e)),
+            },
+            None => None,
+        };
+
+        // Parse for jitter, it can be used only by every timers and it cannot change the order of runs
+        let jitter = match config.get("jitter") {
+            Some(jitter) => {
+                if r#type != TimerType::Every {
+                    return Err(String::from("Property 'jitter' can be used only by every timers, use 'random_delay' instead"));
+                }
+                let jitter = match parse_duration(jitter) {
+                    Ok(j) => j,
+                    Err(e) => return Err(format!("Failed to parse jitter: {}", e)),
+                };
+                if jitter.as_secs() * 2 >= interval.as_secs() {
+                    return Err(String::from("Property 'jitter' must be less than half of 'interval'"));
+                }
+                Some(jitter)
+            }
+            None => None,
+        };
+
+        // Parse that random offsets are stable across restarts
+        let stable_jitter = match config.get("stable_jitter") {
+            Some(stable_jitter) => {
+                if stable_jitter == "yes" {
+                    true
+                }
+                else if stable_jitter == "no" {
+                    false
+                }
+                else {
+                    return Err(String::from("Acceptable values for 'stable_jitter' property: yes or no"));
+                }
+            }
+            None => false,
+        };
+
+        // Parse for validity window, times are interpreted in the time zone of timer
+        let not_before = match config.get("not_before") {
+            Some(not_before) => Some(Self::parse_date_time(not_before, &timezone).map_err(|e| format!("Failed to parse not_before: {}", e))?),
+            None => None,
+        };
+        let not_after = match config.get("not_after") {
+            Some(not_after) => Some(Self::parse_date_time(not_after, &timezone).map_err(|e| format!("Failed to parse not_after: {}", e))?),
+            None => None,
+        };
+        if let (Some(not_before), Some(not_after)) = (not_before, not_after) {
+            if not_before > not_after {
+                return Err(String::from("Property 'not_before' must be earlier than 'not_after'"));
+            }
+        }
+
+        // Parse for run limit, oneshot timers run only once anyway
+        let max_runs = match config.get("max_runs") {
+            Some(max_runs) => {
+                if r#type == TimerType::OneShot {
+                    return Err(String::from("Property 'max_runs' cannot be used by oneshot timers"));
+                }
+                match max_runs.parse::<u64>() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => return Err(String::from("Property 'max_runs' must be a positive number")),
+                }
+            }
+            None => None,
+        };
+
+        // Parse for retry of failed command
+        let retries = match config.get("retries") {
+            Some(retries) => match retries.parse::<u64>() {
+                Ok(n) => n,
+                Err(_) => return Err(String::from("Property 'retries' must be a number")),
+            },
+            None => 0,
+        };
+
+        let retry_delay = match config.get("retry_delay") {
+            Some(retry_delay) => match parse_duration(retry_delay) {
+                Ok(d) => d,
+                Err(e) => return Err(format!("Failed to parse retry_delay: {}", e)),
+            },
+            None => DEFAULT_RETRY_DELAY,
+        };
+
+        let retry_backoff = match config.get("retry_backoff") {
+            Some(retry_backoff) => {
+                if retry_backoff == "fixed" {
+                    RetryBackoff::Fixed
+                }
+                else if retry_backoff == "exponential" {
+                    RetryBackoff::Exponential
+                }
+                else {
+                    return Err(String::from("Acceptable values for 'retry_backoff' property: fixed or exponential"));
+                }
+            }
+            None => RetryBackoff::Fixed,
+        };
+
+        // Parse for calendars which exclude dates, more calendars can be separated by comma
+        let mut exclude: Vec<Calendar> = Vec::new();
+        if let Some(calendars) = config.get("exclude_calendar") {
+            for name in calendars.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+                exclude.push(crate::services::calendar::load(name)?);
+            }
+        }
+
+        let mut timer = Timer::new(id, r#type, interval, command, days, false, schedule);
+        timer.run_as = run_as;
+        timer.timeout = timeout;
+        timer.overlap = overlap;
+        timer.catchup = catchup;
+        timer.dst_policy = dst_policy;
+        timer.timezone = timezone;
+        timer.random_delay = random_delay;
+        timer.jitter = jitter;
+        timer.stable_jitter = stable_jitter;
+        timer.exclude = exclude;
+        timer.day_of_month = day_of_month;
+        timer.not_before = not_before;
+        timer.not_after = not_after;
+        timer.max_runs = max_runs;
+        timer.after = after;
+        timer.on = on;
+        timer.retries = retries;
+        timer.retry_delay = retry_delay;
+        timer.retry_backoff = retry_backoff;
+        // It is not called `group`, because that property is already the primary group of the command
+        timer.concurrency_group = config.get("concurrency_group").cloned();
+        if !times.is_empty() {
+            timer.times = times;
+        }
+        timer.config = config;
+
+        // Some properties above affect the schedule, so calculate it again
+        timer.calculate_next_hit();
 
         return Ok(timer);
     }
 
+    /// Parse date and time in `YYYY-MM-DD HH:MM:SS` or `YYYY-MM-DD` format in the time zone, else in local time zone
+    fn parse_date_time(text: &str, timezone: &Option<Tz>) -> Result<u64, String> {
+        let text = text.trim();
+        let naive = match NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S") {
+            Ok(n) => n,
+            Err(_) => match NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+                Ok(d) => d.and_hms_opt(0, 0, 0).unwrap(),
+                Err(_) => return Err(format!("'{}' must be in YYYY-MM-DD HH:MM:SS or YYYY-MM-DD format", text)),
+            },
+        };
+
+        let time = match timezone {
+            Some(tz) => DstPolicy::Shift.resolve(tz, &naive),
+            None => DstPolicy::Shift.resolve(&Local, &naive),
+        };
+
+        return match time {
+            Some(t) => Ok(t),
+            None => Err(format!("'{}' is not a valid time", text)),
+        };
+    }
+
     /// Calculate when the timer should run next time
     pub fn calculate_next_hit(&mut self) {
-        verbose_println!("calculate_next_hit: {}: Calculate next hit", self.id);
-        verbose_println!("calculate_next_hit: {}: Timer type: {}", self.id, self.r#type);
-        verbose_println!("calculate_next_hit: {}: Days: {:?}", self.id, self.days);
+        self.calculate_next_hit_skipping();
+    }
 
+    /// Calculate when the timer should run next time, it returns the dates which are skipped before the next hit,
+    /// because they are excluded by a calendar (date and calendar name)
+    ///
+    /// Scheduler uses it after a run, so every skipped date is reported once.
+    pub fn calculate_next_hit_skipping(&mut self) -> Vec<(NaiveDate, String)> {
         let now = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
             Ok(n) => n.as_secs(),
             Err(e) => panic!("Failed for calculate time since UNIX_EPICH: {}", e),
         };
-        verbose_println!("calculate_next_hit: {}: Time now: {}", self.id, now);
-        let tz_diff = Local::now().offset().utc_minus_local();
 
-        let last_midnight = if tz_diff < 0 {
-            now - now % 86400 - tz_diff.abs() as u64
+        // Schedule is continued from the time without offset, so offsets do not accumulate
+        let mut skipped: Vec<(NaiveDate, String)> = Vec::new();
+        let scheduled = self.next_hit_excluding((now as i64 - self.offset).max(0) as u64, |date, calendar| {
+            skipped.push((date, String::from(calendar)));
+        });
+        if scheduled == u64::MAX {
+            self.offset = 0;
+            self.next_hit = scheduled;
+            return skipped;
         }
-        else {
-            now - now % 86400 + tz_diff.abs() as u64
-        };
-        let interval = self.interval.as_secs();
-        verbose_println!("calculate_next_hit: {}: Last midnight: {}", self.id, last_midnight);
-        verbose_println!("calculate_next_hit: {}: Interval ins seconds: {}", self.id, interval);
 
-        if self.r#type == TimerType::At {
-            let days_until_next = self.calculate_next_day_diff_index();
-            verbose_println!("calculate_next_hit: {}: Days until next run: {}", self.id, days_until_next);
+        self.offset = self.random_offset();
+        self.next_hit = (scheduled as i64 + self.offset).max(0) as u64;
+        if self.offset != 0 {
+            verbose_println!("calculate_next_hit: {}: Scheduled at {}, offset is {} seconds", self.id, scheduled, self.offset);
+        }
+        return skipped;
+    }
+
+    /// When the timer should run next time without random offset, seconds since UNIX_EPOCH
+    pub fn scheduled_hit(&self) -> u64 {
+        return (self.next_hit as i64 - self.offset).max(0) as u64;
+    }
+
+    /// Choose random offset according to `random_delay` and `jitter` properties, seconds
+    fn random_offset(&self) -> i64 {
+        let mut offset: i64 = 0;
 
-            self.next_hit = interval + last_midnight + days_until_next * 86400;
-            verbose_println!("calculate_next_hit: {}: Next hit: {}", self.id, self.next_hit);
+        if let Some(random_delay) = &self.random_delay {
+            offset += self.random_number("random_delay", random_delay.as_secs() + 1) as i64;
         }
-        else {
-            let days_until_next = self.calculate_next_day_diff_index();
-            verbose_println!("calculate_next_hit: {}: Days until next run: {}", self.id, days_until_next);
 
-            if days_until_next == 0 {
-                self.next_hit = interval + now;
-            }
-            else {
-                self.next_hit = interval + last_midnight + days_until_next * 86400
-            }
-            verbose_println!("calculate_next_hit: {}: Next hit: {}", self.id, self.next_hit);
+        if let Some(jitter) = &self.jitter {
+            let jitter = jitter.as_secs();
+            offset += self.random_number("jitter", jitter * 2 + 1) as i64 - jitter as i64;
         }
+
+        return offset;
     }
 
-    /// Internally used by `calculate_next_hit`. This function checks which is that day when timer can run again
-    fn calculate_next_day_diff_index(&self) -> u64 {
-        let mut difference: u64 = 0;
+    /// Random number from 0 until `range` (exclusive), it is derived from host name and timer id if jitter is stable
+    fn random_number(&self, purpose: &str, range: u64) -> u64 {
+        if self.stable_jitter {
+            return Self::stable_hash(&format!("{}/{}/{}", Self::hostname(), self.id, purpose)) % range;
+        }
+        return rand::thread_rng().gen_range(0..range);
+    }
 
-        let today_index = chrono::Local::now().weekday().num_days_from_monday() as usize;
-        verbose_println!("calculate_next_day_diff_index: {}: Today index is {}", self.id, today_index);
+    /// Name of the host, it is empty if it cannot be read
+    fn hostname() -> String {
+        let mut buffer = vec![0 as libc::c_char; 256];
+        let rc = unsafe { libc::gethostname(buffer.as_mut_ptr(), buffer.len()) };
+        if rc != 0 {
+            return String::new();
+        }
+        return unsafe { std::ffi::CStr::from_ptr(buffer.as_ptr()) }.to_string_lossy().to_string();
+    }
 
-        let now = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
-            Ok(n) => n.as_secs(),
-            Err(e) => panic!("Failed for calculate time since UNIX_EPICH: {}", e),
+    /// FNV-1a hash, unlike the hasher of standard library it is guaranteed to be the same in every version
+    fn stable_hash(text: &str) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in text.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        return hash;
+    }
+
+    /// Calculate when the timer should run after a specific time, seconds since UNIX_EPOCH
+    pub fn next_hit_from(&self, now: u64) -> u64 {
+        return self.next_hit_excluding(now, |_, _| ());
+    }
+
+    /// Calculate when the timer should run after a specific time, `on_skip` is called with every
+    /// date which is skipped because it is excluded by a calendar
+    fn next_hit_excluding<F: FnMut(NaiveDate, &str)>(&self, now: u64, on_skip: F) -> u64 {
+        // Timer does not run before its validity window
+        let from = match self.not_before {
+            Some(not_before) if not_before > now => match self.r#type {
+                // Interval is counted from the start of window
+                TimerType::Every | TimerType::OneShot => not_before,
+                // Time of day can be exactly the start of window
+                _ => not_before - 1,
+            },
+            _ => now,
         };
 
-        // let secs_since_midnight = now % 86400;
-        let tz_diff = Local::now().offset().utc_minus_local();
+        let hit = match &self.timezone {
+            Some(tz) => self.next_hit_excluding_in(tz, from, on_skip),
+            None => self.next_hit_excluding_in(&Local, from, on_skip),
+        };
 
-        let secs_since_midnight = if tz_diff < 0 {
-            now % 86400 + tz_diff.abs() as u64
+        // Timer does not run after its validity window
+        if let Some(not_after) = self.not_after {
+            if hit > not_after {
+                return u64::MAX;
+            }
         }
-        else {
-            now % 86400 - tz_diff.abs() as u64
+
+        return hit;
+    }
+
+    /// Delay after the specified failed attempt (counted from 1) before the next attempt
+    pub fn retry_delay_of(&self, attempt: u64) -> Duration {
+        return match self.retry_backoff {
+            RetryBackoff::Fixed => self.retry_delay,
+            RetryBackoff::Exponential => {
+                let factor = 1u32.checked_shl(attempt.saturating_sub(1) as u32).unwrap_or(u32::MAX);
+                self.retry_delay.checked_mul(factor).unwrap_or(MAX_RETRY_DELAY).min(MAX_RETRY_DELAY)
+            }
         };
+    }
 
-        // Check that timer can be scheduled today
-        let today_next_hit_theory = if self.r#type == TimerType::At {
-            self.interval.as_secs()
+    /// How many runs are left before the timer is removed, None if there is no limit
+    pub fn remaining_runs(&self) -> Option<u64> {
+        return self.max_runs.map(|max_runs| max_runs.saturating_sub(self.runs));
+    }
+
+    /// Time when the scheduler has to deal with the timer, seconds since UNIX_EPOCH
+    ///
+    /// It is the next hit, or the end of validity window if it is earlier, so the timer is retired in time.
+    /// Timers which have done all of their runs are due right now, paused timers only at the end of window.
+    pub fn deadline(&self) -> u64 {
+        if self.remaining_runs() == Some(0) {
+            return 0;
         }
-        else {
-            self.interval.as_secs() + secs_since_midnight
+
+        let next_hit = if self.paused { u64::MAX } else { self.next_hit };
+        return match self.not_after {
+            Some(not_after) => next_hit.min(not_after),
+            None => next_hit,
         };
+    }
+
+    /// Check that validity window of timer has ended, so it can be retired
+    pub fn expired(&self, now: u64) -> bool {
+        return match self.not_after {
+            Some(not_after) => not_after <= now,
+            None => false,
+        };
+    }
 
-        verbose_println!("calculcate_next_day_diff_index: {}: Today next theoretically hit: {}", self.id, today_next_hit_theory);
-        verbose_println!("calculcate_next_day_diff_index: {}: Seconds since midnight: {}", self.id, secs_since_midnight);
+    /// Calculate when the timer should run after a specific time in the specified time zone,
+    /// dates which are excluded by calendars are skipped
+    fn next_hit_excluding_in<Tz: TimeZone, F: FnMut(NaiveDate, &str)>(&self, tz: &Tz, now: u64, mut on_skip: F) -> u64 {
+        let mut from = now;
+        for _ in 0..MAX_EXCLUDED_DAYS {
+            let hit = self.next_hit_in(tz, from);
+            if hit == u64::MAX || self.exclude.is_empty() {
+                return hit;
+            }
+
+            let date = match tz.timestamp_opt(hit as i64, 0).single() {
+                Some(d) => d.date_naive(),
+                None => return u64::MAX,
+            };
+
+            let calendar = match self.exclude.iter().find(|x| x.contains(&date)) {
+                Some(c) => c,
+                None => return hit,
+            };
+            on_skip(date, &calendar.name);
 
-        if self.r#type != TimerType::At && today_next_hit_theory <= 86400 && today_next_hit_theory > secs_since_midnight && self.days[today_index] == 'X' {
-            return difference;
+            // Continue from the end of the excluded day
+            let next_midnight = date.succ_opt().and_then(|x| x.and_hms_opt(0, 0, 0)).and_then(|x| DstPolicy::Shift.resolve(tz, &x));
+            from = match next_midnight {
+                Some(m) => m - 1,
+                None => return u64::MAX,
+            };
         }
 
-        if self.r#type == TimerType::At && today_next_hit_theory > secs_since_midnight && self.days[today_index] == 'X' {
-            return difference;
+        return u64::MAX;
+    }
+
+    /// Calculate when the timer should run after a specific time in the specified time zone
+    ///
+    /// Days and time of days are interpreted as local date and time of the zone, so DST changes are
+    /// handled by the time zone. Time which does not exist due to DST change is handled by `dst_policy`,
+    /// time which exists twice is used only at its first occurrence.
+    pub fn next_hit_in<Tz: TimeZone>(&self, tz: &Tz, now: u64) -> u64 {
+        verbose_println!("next_hit_in: {}: Calculate next hit", self.id);
+        verbose_println!("next_hit_in: {}: Timer type: {}", self.id, self.r#type);
+        verbose_println!("next_hit_in: {}: Days: {:?}", self.id, self.days);
+        verbose_println!("next_hit_in: {}: Time now: {}", self.id, now);
+
+        let next_hit = match self.r#type {
+            TimerType::Cron => match &self.schedule {
+                Some(schedule) => schedule.next_after(tz, now, self.dst_policy),
+                None => None,
+            },
+            TimerType::At => self.next_at_hit(tz, now),
+            TimerType::Every | TimerType::OneShot => self.next_every_hit(tz, now),
+            // It is not driven by time, but by the preceding timer
+            TimerType::After => None,
+        };
+
+        let next_hit = next_hit.unwrap_or(u64::MAX);
+        verbose_println!("next_hit_in: {}: Next hit: {}", self.id, next_hit);
+        return next_hit;
+    }
+
+    /// Internally used by `next_hit_in`. Find the first allowed day when a time of day is later than now.
+    fn next_at_hit<Tz: TimeZone>(&self, tz: &Tz, now: u64) -> Option<u64> {
+        let mut date = tz.timestamp_opt(now as i64, 0).single()?.date_naive();
+
+        // Days of month can be rare (e.g. 31st on Monday), so it is searched as long as in cron schedule
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.day_allowed(&date) {
+                // Time shifted by DST can be later than the next time of day, so the earliest one is looked for
+                let mut first: Option<u64> = None;
+                for time in &self.times {
+                    let time = NaiveTime::from_num_seconds_from_midnight_opt(time.as_secs() as u32, 0)?;
+                    match self.dst_policy.resolve(tz, &date.and_time(time)) {
+                        Some(hit) if hit > now && first.is_none_or(|x| hit < x) => first = Some(hit),
+                        Some(_) => (),
+                        None => verbose_println!("next_at_hit: {}: {} {} does not exist, it is skipped", self.id, date, time),
+                    }
+                }
+                if first.is_some() {
+                    return first;
+                }
+            }
+            date = date.succ_opt()?;
         }
 
-        // Timer cannot be schedule today, so find next good day
-        let next_index = today_index + 1 % (self.days.len() - 1);
-        difference += 1;
-        verbose_println!("calculcate_next_day_diff_index: {}: Check available day from: {}", self.id, next_index);
-        
+        return None;
+    }
+
+    /// Internally used by `next_hit_in`. Interval is added to now if it is still on an allowed day,
+    /// else the interval is counted from the midnight of the next allowed day.
+    fn next_every_hit<Tz: TimeZone>(&self, tz: &Tz, now: u64) -> Option<u64> {
+        let interval = self.interval.as_secs();
+        let today = tz.timestamp_opt(now as i64, 0).single()?.date_naive();
+        let theory = now + interval;
+        let theory_date = tz.timestamp_opt(theory as i64, 0).single()?.date_naive();
 
-        for i in next_index..self.days.len() {
-            verbose_println!("Checking [{}] -> {}; Current difference: {}", i, self.days[i], difference);
-            if self.days[i] == 'X' {
-                return difference;
+        // Interval which is longer than a day is not restarted at midnight, it is only postponed
+        // to the start of the next allowed day if it would hit on a not allowed day
+        if interval >= SECONDS_PER_DAY {
+            if self.day_allowed(&theory_date) {
+                return Some(theory);
             }
-            difference += 1;
+            return self.next_allowed_midnight(tz, &theory_date);
+        }
+
+        if self.day_allowed(&today) && theory_date == today {
+            return Some(theory);
         }
 
-        for i in 0..next_index {
-            verbose_println!("Checking [{}] -> {}; Current difference: {}", i, self.days[i], difference);
-            if self.days[i] == 'X' {
-                return difference;
+        return Some(self.next_allowed_midnight(tz, &today)? + interval);
+    }
+
+    /// Calculate the start of the first allowed day after the specified day, seconds since UNIX_EPOCH
+    fn next_allowed_midnight<Tz: TimeZone>(&self, tz: &Tz, day: &NaiveDate) -> Option<u64> {
+        let mut date = day.succ_opt()?;
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.day_allowed(&date) {
+                // Midnight can also be skipped by DST change in some zones, then day starts later
+                return DstPolicy::Shift.resolve(tz, &date.and_hms_opt(0, 0, 0)?);
             }
-            difference += 1;
+            date = date.succ_opt()?;
         }
 
-        verbose_println!("calculcate_next_day_diff_index: {}: difference until next day: {}", self.id, difference);
-        return difference;
+        return None;
+    }
+
+    /// Check that timer can run on the specified day, according to `days` and `day_of_month` properties
+    fn day_allowed(&self, date: &NaiveDate) -> bool {
+        if self.days[date.weekday().num_days_from_monday() as usize] != 'X' {
+            return false;
+        }
+        return match &self.day_of_month {
+            Some(day_of_month) => day_of_month.matches(date),
+            None => true,
+        };
+    }
+
+    /// Count how many times the timer should have run after `since` until `now` (both are seconds since UNIX_EPOCH)
+    pub fn missed_runs(&self, since: u64, now: u64) -> u64 {
+        let mut count = 0;
+        let mut hit = self.next_hit_from(since);
+        while hit <= now && count < MAX_MISSED_RUNS {
+            count += 1;
+            hit = self.next_hit_from(hit);
+        }
+        return count;
     }
 
     /// Check that timer should run, depend that what time is it now
     pub fn should_run(&self, now: u64) -> bool {
-        if self.next_hit <= now {
+        if !self.paused && self.next_hit <= now {
             return true;
         }
         return false;
     }
 
     /// Execute command which belong to timer
-    pub async fn execute(&self) -> Option<(Vec<CommandOutput>, i32)> {
+    ///
+    /// Command runs in its own process group. If timeout is set and command runs longer, then the whole
+    /// group gets SIGTERM, then SIGKILL if it is still alive after the grace period.
+    /// The `on_spawn` is called with the process group id after command is started.
+    /// It blocks until the command has ended, so it must not be called on a runtime worker.
+    pub fn execute<F: FnOnce(libc::pid_t)>(&self, on_spawn: F) -> Option<CommandResult> {
         if self.command.len() == 0 {
             verbose_println!("execute: {}: Command vector is empty", self.id);
             return None;
@@ -248,40 +905,143 @@ impl Timer {
         verbose_println!("execute: {}: Command argument: /usr/bin/bash -c \"{}\"", self.id, arg);
         cmd.arg(arg);
 
-        let mut child = cmd.stdout(Stdio::piped())
+        if let Some(run_as) = &self.run_as {
+            verbose_println!("execute: {}: Run as user {} ({}) with group {}", self.id, run_as.user, run_as.uid, run_as.gid);
+            cmd.env("HOME", &run_as.home);
+            cmd.env("USER", &run_as.user);
+            cmd.env("LOGNAME", &run_as.user);
+
+            // Drop privileges only if it is needed, a non-root daemon could not do it anyway
+            let (uid, gid) = (run_as.uid, run_as.gid);
+            let same_user = unsafe { libc::geteuid() == uid && libc::getegid() == gid };
+            if !same_user {
+                let groups = run_as.groups();
+                unsafe {
+                    cmd.pre_exec(move || {
+                        if libc::setgroups(groups.len() as _, groups.as_ptr()) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                        if libc::setgid(gid) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                        if libc::setuid(uid) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                        Ok(())
+                    });
+                }
+            }
+        }
+
+        let child = cmd.stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .spawn()
-            .unwrap();
+            .process_group(0)
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Failed to start command of {}: {}", self.id, e);
+                let output = CommandOutput {
+                    time: crate::services::file::time_is_now(),
+                    text: format!("Failed to start command: {}", e),
+                    r#type: CommandOutputType::Error,
+                };
+                return Some(CommandResult { output: vec![output], code: -999, status: ExecutionStatus::Failed });
+            }
+        };
+
+        on_spawn(child.id() as libc::pid_t);
+
+        // Start a watchdog which kills the process group if command runs too long
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let (done_sender, done_receiver) = mpsc::channel::<()>();
+        let watchdog = match self.timeout {
+            Some(timeout) => {
+                let pgid = child.id() as libc::pid_t;
+                let timed_out = timed_out.clone();
+                let id = self.id.clone();
+                Some(std::thread::spawn(move || {
+                    if let Err(mpsc::RecvTimeoutError::Timeout) = done_receiver.recv_timeout(timeout) {
+                        println!("Timeout: {}: Command runs longer than {} seconds, send SIGTERM to process group {}", id, timeout.as_secs(), pgid);
+                        timed_out.store(true, Ordering::SeqCst);
+                        unsafe { libc::kill(-pgid, libc::SIGTERM) };
+
+                        if let Err(mpsc::RecvTimeoutError::Timeout) = done_receiver.recv_timeout(KILL_GRACE_PERIOD) {
+                            println!("Timeout: {}: Process group {} is still alive, send SIGKILL", id, pgid);
+                            unsafe { libc::kill(-pgid, libc::SIGKILL) };
+                        }
+                    }
+                }))
+            }
+            None => None,
+        };
 
         let mut stdout: Vec<CommandOutput> = Vec::new();
         let mut stderr: Vec<CommandOutput> = Vec::new();
+        let stdout_pipe = child.stdout.take();
+        let stderr_pipe = child.stderr.take();
 
-        std::thread::scope(|spawner| {
+        // Watchdog runs until the command has exited and its pipes are closed: command can close its
+        // output before it ends, or leave a background process behind which keeps the pipes open
+        let status = std::thread::scope(|spawner| {
             spawner.spawn(|| {
-                let pipe = child.stdout.as_mut().unwrap();
-                stdout = crate::services::file::read_buffer(&mut BufReader::new(pipe), CommandOutputType::Info);
+                if let Some(mut pipe) = stdout_pipe {
+                    stdout = crate::services::file::read_buffer(&mut BufReader::new(&mut pipe), CommandOutputType::Info);
+                }
             });
             spawner.spawn(|| {
-                let pipe = child.stderr.as_mut().unwrap();
-                stderr = crate::services::file::read_buffer(&mut BufReader::new(pipe), CommandOutputType::Error);
+                if let Some(mut pipe) = stderr_pipe {
+                    stderr = crate::services::file::read_buffer(&mut BufReader::new(&mut pipe), CommandOutputType::Error);
+                }
             });
 
+            return child.wait();
         });
 
         stdout.append(&mut stderr);
         stdout.sort_by(|a, b| a.time.cmp(&b.time));
 
-        let status = child.wait();
+        // Command is ended, watchdog can stop
+        drop(done_sender);
+        if let Some(watchdog) = watchdog {
+            let _ = watchdog.join();
+        }
+
+        if timed_out.load(Ordering::SeqCst) {
+            stdout.push(CommandOutput {
+                time: crate::services::file::time_is_now(),
+                text: format!("Command is timed out after {} seconds, process group was killed", self.timeout.unwrap_or_default().as_secs()),
+                r#type: CommandOutputType::Error,
+            });
+        }
+
         verbose_println!("execute: {}: Command end status: {:?}", self.id, status);
         let status = match status {
             Ok(s) => s,
             Err(e) => {
                 eprintln!("Failed to wait for child: {}", e);
-                return Some((stdout, -999));
+                return Some(CommandResult { output: stdout, code: -999, status: ExecutionStatus::Failed });
             }
         };
-        
-        return Some((stdout, status.code().unwrap()));
+
+        // If command was killed by signal, report it like shell does
+        let code = match status.code() {
+            Some(code) => code,
+            None => 128 + status.signal().unwrap_or(0),
+        };
+
+        let status = if timed_out.load(Ordering::SeqCst) {
+            ExecutionStatus::TimedOut
+        }
+        else if code == 0 {
+            ExecutionStatus::Ok
+        }
+        else {
+            ExecutionStatus::Failed
+        };
+
+        return Some(CommandResult { output: stdout, code, status });
     }
 }
 
@@ -294,4 +1054,302 @@ impl PartialEq for Timer {
     }
 }
 
-impl Eq for Timer {}
\ No newline at end of file
+impl Eq for Timer {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use chrono_tz::Europe::Budapest;
+
+    // Budapest switches to summer time on 2026-03-29 at 02:00 (CET -> CEST)
+    // and back to winter time on 2026-10-25 at 03:00 (CEST -> CET).
+
+    fn try_timer(properties: &[(&str, &str)]) -> Result<Timer, String> {
+        let mut config: HashMap<String, String> = HashMap::new();
+        config.insert(String::from("id"), String::from("test"));
+        config.insert(String::from("command"), String::from("true"));
+        for (key, value) in properties {
+            config.insert(String::from(*key), String::from(*value));
+        }
+        return Timer::from_config(config);
+    }
+
+    fn timer(properties: &[(&str, &str)]) -> Timer {
+        return try_timer(properties).unwrap();
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> u64 {
+        return Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap().timestamp() as u64;
+    }
+
+    #[test]
+    fn at_keeps_local_time_after_spring_forward() {
+        let timer = timer(&[("type", "at"), ("interval", "07:00:00")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 28, 12, 0)), utc(2026, 3, 29, 5, 0));
+    }
+
+    #[test]
+    fn at_keeps_local_time_after_fall_back() {
+        let timer = timer(&[("type", "at"), ("interval", "07:00:00")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 10, 24, 12, 0)), utc(2026, 10, 25, 6, 0));
+    }
+
+    #[test]
+    fn at_in_spring_forward_gap_is_skipped() {
+        let timer = timer(&[("type", "at"), ("interval", "02:30:00"), ("dst_policy", "skip")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 28, 12, 0)), utc(2026, 3, 30, 0, 30));
+    }
+
+    #[test]
+    fn at_in_spring_forward_gap_is_shifted() {
+        let timer = timer(&[("type", "at"), ("interval", "02:30:00"), ("dst_policy", "shift")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 28, 12, 0)), utc(2026, 3, 29, 1, 30));
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 29, 1, 30)), utc(2026, 3, 30, 0, 30));
+    }
+
+    #[test]
+    fn at_in_fall_back_overlap_runs_once() {
+        let timer = timer(&[("type", "at"), ("interval", "02:30:00")]);
+        let first = timer.next_hit_in(&Budapest, utc(2026, 10, 24, 12, 0));
+        assert_eq!(first, utc(2026, 10, 25, 0, 30));
+        assert_eq!(timer.next_hit_in(&Budapest, first), utc(2026, 10, 26, 1, 30));
+    }
+
+    #[test]
+    fn at_respects_days_around_spring_forward() {
+        let timer = timer(&[("type", "at"), ("interval", "07:00:00"), ("days", "X______")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 28, 12, 0)), utc(2026, 3, 30, 5, 0));
+    }
+
+    #[test]
+    fn every_starts_from_local_midnight_of_next_day() {
+        let timer = timer(&[("type", "every"), ("interval", "01:00:00"), ("days", "______X")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 28, 12, 0)), utc(2026, 3, 29, 0, 0));
+    }
+
+    #[test]
+    fn every_interval_is_absolute_during_fall_back() {
+        let timer = timer(&[("type", "every"), ("interval", "01:00:00")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 10, 25, 0, 30)), utc(2026, 10, 25, 1, 30));
+    }
+
+    #[test]
+    fn cron_in_spring_forward_gap_is_skipped() {
+        let timer = timer(&[("type", "cron"), ("schedule", "30 2 * * *"), ("dst_policy", "skip")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 28, 12, 0)), utc(2026, 3, 30, 0, 30));
+    }
+
+    #[test]
+    fn cron_in_spring_forward_gap_is_shifted() {
+        let timer = timer(&[("type", "cron"), ("schedule", "30 2 * * *"), ("dst_policy", "shift")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 28, 12, 0)), utc(2026, 3, 29, 1, 30));
+    }
+
+    #[test]
+    fn cron_in_fall_back_overlap_runs_once() {
+        let timer = timer(&[("type", "cron"), ("schedule", "30 2 * * *")]);
+        let first = timer.next_hit_in(&Budapest, utc(2026, 10, 24, 12, 0));
+        assert_eq!(first, utc(2026, 10, 25, 0, 30));
+        assert_eq!(timer.next_hit_in(&Budapest, first), utc(2026, 10, 26, 1, 30));
+    }
+
+    #[test]
+    fn timezone_property_is_used_for_schedule() {
+        let timer = timer(&[("type", "at"), ("interval", "09:00:00"), ("timezone", "America/New_York")]);
+        assert_eq!(timer.next_hit_from(utc(2026, 3, 28, 14, 0)), utc(2026, 3, 29, 13, 0));
+        assert_eq!(timer.next_hit_from(utc(2026, 11, 1, 12, 0)), utc(2026, 11, 1, 14, 0));
+    }
+
+    #[test]
+    fn unknown_timezone_is_rejected() {
+        assert!(try_timer(&[("type", "at"), ("interval", "09:00:00"), ("timezone", "Mars/Olympus_Mons")]).is_err());
+    }
+
+    #[test]
+    fn every_interval_can_be_longer_than_a_day() {
+        let timer = timer(&[("type", "every"), ("interval", "3d")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 27, 12, 0)), utc(2026, 3, 30, 12, 0));
+    }
+
+    #[test]
+    fn every_long_interval_is_postponed_to_next_allowed_day() {
+        // 2026-03-30 is a Monday, only Tuesday is allowed
+        let timer = timer(&[("type", "every"), ("interval", "3d"), ("days", "_X_____")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 27, 12, 0)), utc(2026, 3, 30, 22, 0));
+    }
+
+    #[test]
+    fn at_interval_must_be_time_of_day() {
+        assert!(try_timer(&[("type", "at"), ("interval", "25:00:00")]).is_err());
+    }
+
+    #[test]
+    fn stable_jitter_is_the_same_after_restart() {
+        let properties = [("type", "at"), ("interval", "07:00:00"), ("random_delay", "5m"), ("stable_jitter", "yes")];
+        let first = timer(&properties);
+        let second = timer(&properties);
+        assert_eq!(first.offset, second.offset);
+        assert!(first.offset >= 0 && first.offset <= 300);
+        assert_eq!(first.next_hit, first.scheduled_hit() + first.offset as u64);
+    }
+
+    #[test]
+    fn jitter_stays_within_window() {
+        for n in 0..100 {
+            let id = format!("test{}", n);
+            let timer = timer(&[("id", &id), ("type", "every"), ("interval", "10m"), ("jitter", "1m"), ("stable_jitter", "yes")]);
+            assert!(timer.offset >= -60 && timer.offset <= 60);
+        }
+    }
+
+    #[test]
+    fn jitter_is_only_for_short_enough_every_intervals() {
+        assert!(try_timer(&[("type", "every"), ("interval", "10m"), ("jitter", "5m")]).is_err());
+        assert!(try_timer(&[("type", "at"), ("interval", "10m"), ("jitter", "1m")]).is_err());
+    }
+
+    #[test]
+    fn excluded_dates_are_skipped() {
+        let mut timer = timer(&[("type", "at"), ("interval", "07:00:00"), ("timezone", "Europe/Budapest")]);
+        timer.exclude = vec![Calendar::parse("holidays", "# Easter\n2026-04-05..2026-04-06\n").unwrap()];
+
+        let mut skipped: Vec<String> = Vec::new();
+        let hit = timer.next_hit_excluding(utc(2026, 4, 4, 12, 0), |date, calendar| skipped.push(format!("{} {}", date, calendar)));
+        assert_eq!(hit, utc(2026, 4, 7, 5, 0));
+        assert_eq!(skipped, vec!["2026-04-05 holidays", "2026-04-06 holidays"]);
+    }
+
+    #[test]
+    fn every_timer_skips_whole_excluded_day() {
+        let mut timer = timer(&[("type", "every"), ("interval", "00:30:00"), ("timezone", "Europe/Budapest")]);
+        timer.exclude = vec![Calendar::parse("holidays", "2026-12-25").unwrap()];
+        assert_eq!(timer.next_hit_from(utc(2026, 12, 24, 22, 50)), utc(2026, 12, 25, 23, 30));
+    }
+
+    #[test]
+    fn at_runs_at_every_time_of_day() {
+        let timer = timer(&[("type", "at"), ("interval", "18:00:00, 07:00:00, 12:30:00"), ("timezone", "Europe/Budapest")]);
+        let first = timer.next_hit_from(utc(2026, 5, 4, 4, 0));
+        let second = timer.next_hit_from(first);
+        let third = timer.next_hit_from(second);
+        assert_eq!((first, second, third), (utc(2026, 5, 4, 5, 0), utc(2026, 5, 4, 10, 30), utc(2026, 5, 4, 16, 0)));
+        assert_eq!(timer.next_hit_from(third), utc(2026, 5, 5, 5, 0));
+    }
+
+    #[test]
+    fn at_times_keep_order_when_dst_shifts_one() {
+        let timer = timer(&[("type", "at"), ("times", "02:30:00, 03:00:00")]);
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 28, 23, 0)), utc(2026, 3, 29, 1, 0));
+        assert_eq!(timer.next_hit_in(&Budapest, utc(2026, 3, 29, 1, 0)), utc(2026, 3, 29, 1, 30));
+    }
+
+    #[test]
+    fn last_day_of_month_handles_leap_years() {
+        let timer = timer(&[("type", "at"), ("interval", "12:00:00"), ("day_of_month", "last"), ("timezone", "UTC")]);
+        assert_eq!(timer.next_hit_from(utc(2027, 2, 1, 0, 0)), utc(2027, 2, 28, 12, 0));
+        assert_eq!(timer.next_hit_from(utc(2028, 2, 1, 0, 0)), utc(2028, 2, 29, 12, 0));
+        assert_eq!(timer.next_hit_from(utc(2028, 2, 29, 12, 0)), utc(2028, 3, 31, 12, 0));
+    }
+
+    #[test]
+    fn day_of_month_list_skips_short_months() {
+        let timer = timer(&[("type", "at"), ("interval", "12:00:00"), ("day_of_month", "1, 31"), ("timezone", "UTC")]);
+        assert_eq!(timer.next_hit_from(utc(2026, 4, 1, 12, 0)), utc(2026, 5, 1, 12, 0));
+        assert_eq!(timer.next_hit_from(utc(2026, 5, 1, 12, 0)), utc(2026, 5, 31, 12, 0));
+    }
+
+    #[test]
+    fn nth_weekday_of_month() {
+        let timer = timer(&[("type", "at"), ("interval", "12:00:00"), ("day_of_month", "first monday, last friday"), ("timezone", "UTC")]);
+        assert_eq!(timer.next_hit_from(utc(2026, 5, 1, 13, 0)), utc(2026, 5, 4, 12, 0));
+        assert_eq!(timer.next_hit_from(utc(2026, 5, 4, 12, 0)), utc(2026, 5, 29, 12, 0));
+        assert_eq!(timer.next_hit_from(utc(2026, 5, 29, 12, 0)), utc(2026, 6, 1, 12, 0));
+    }
+
+    #[test]
+    fn day_of_month_and_days_must_both_match() {
+        // 2026-08-31 is the first Monday on the 31st after 2026-04-01
+        let timer = timer(&[("type", "at"), ("interval", "12:00:00"), ("day_of_month", "31"), ("days", "X______"), ("timezone", "UTC")]);
+        assert_eq!(timer.next_hit_from(utc(2026, 4, 1, 0, 0)), utc(2026, 8, 31, 12, 0));
+    }
+
+    #[test]
+    fn timer_runs_only_in_validity_window() {
+        let timer = timer(&[
+            ("type", "at"), ("interval", "12:00:00"), ("timezone", "UTC"),
+            ("not_before", "2026-11-01 00:00:00"), ("not_after", "2026-11-03"),
+        ]);
+        assert_eq!(timer.next_hit_from(utc(2026, 10, 20, 0, 0)), utc(2026, 11, 1, 12, 0));
+        assert_eq!(timer.next_hit_from(utc(2026, 11, 1, 12, 0)), utc(2026, 11, 2, 12, 0));
+        assert_eq!(timer.next_hit_from(utc(2026, 11, 2, 12, 0)), u64::MAX);
+        assert!(!timer.expired(utc(2026, 11, 2, 23, 59)));
+        assert!(timer.expired(utc(2026, 11, 3, 0, 0)));
+    }
+
+    #[test]
+    fn every_interval_is_counted_from_start_of_validity_window() {
+        let timer = timer(&[("type", "every"), ("interval", "1h"), ("timezone", "UTC"), ("not_before", "2026-11-01 00:00:00")]);
+        let first = timer.next_hit_from(utc(2026, 10, 20, 0, 0));
+        assert_eq!(first, utc(2026, 11, 1, 1, 0));
+        assert_eq!(timer.next_hit_from(first), utc(2026, 11, 1, 2, 0));
+    }
+
+    #[test]
+    fn max_runs_is_only_for_recurring_timers() {
+        let timer = timer(&[("type", "every"), ("interval", "10m"), ("max_runs", "10")]);
+        assert_eq!(timer.remaining_runs(), Some(10));
+        assert!(try_timer(&[("type", "oneshot"), ("interval", "10m"), ("max_runs", "10")]).is_err());
+    }
+
+    #[test]
+    fn after_timer_is_not_driven_by_time() {
+        let timer = timer(&[("after", "backup"), ("on", "failure")]);
+        assert!(timer.r#type == TimerType::After);
+        assert!(timer.on.matches(&ExecutionStatus::TimedOut));
+        assert!(!timer.on.matches(&ExecutionStatus::Ok));
+        assert_eq!(timer.next_hit, u64::MAX);
+    }
+
+    #[test]
+    fn exponential_retry_delay_doubles() {
+        let timer = timer(&[("type", "every"), ("interval", "1h"), ("retries", "3"), ("retry_delay", "30s"), ("retry_backoff", "exponential")]);
+        let delays = (1..=3).map(|x| timer.retry_delay_of(x).as_secs()).collect::<Vec<u64>>();
+        assert_eq!(delays, vec![30, 60, 120]);
+        assert_eq!(timer.retry_delay_of(64), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn paused_timer_does_not_run() {
+        let mut timer = timer(&[("type", "every"), ("interval", "10m")]);
+        let due = timer.next_hit;
+        assert!(timer.should_run(due));
+
+        timer.paused = true;
+        assert!(!timer.should_run(due));
+    }
+
+    #[test]
+    fn deadline_covers_end_of_window_and_finished_runs() {
+        let mut timer = timer(&[("type", "every"), ("interval", "10m"), ("max_runs", "2")]);
+        assert_eq!(timer.deadline(), timer.next_hit);
+
+        timer.paused = true;
+        assert_eq!(timer.deadline(), u64::MAX);
+
+        timer.not_after = Some(timer.next_hit + 60);
+        assert_eq!(timer.deadline(), timer.next_hit + 60);
+
+        timer.runs = 2;
+        assert_eq!(timer.deadline(), 0);
+    }
+
+    #[test]
+    fn timeout_kills_command_which_closes_its_output() {
+        let timer = timer(&[("type", "every"), ("interval", "1h"), ("timeout", "1s"), ("command", "exec >/dev/null 2>&1; sleep 99999")]);
+        let begin = std::time::Instant::now();
+        let result = timer.execute(|_| ()).unwrap();
+        assert!(result.status == ExecutionStatus::TimedOut);
+        assert!(begin.elapsed() < KILL_GRACE_PERIOD);
+    }
+}
@@ -48,15 +48,20 @@ pub enum Action {
         #[arg(short, long)]
         id: String,
 
-        /// Timer type: every, at or oneshot. Mandatory for create action.
+        /// Timer type: every, at, oneshot, cron or after. Mandatory for create action.
         #[arg(short, long)]
         #[arg(value_parser = validate_type)]
         r#type: String,
 
-        /// Timer interval in HH:MM:SS format. Mandatory for create action.
-        #[arg(short = 'I', long)]
+        /// Timer interval in HH:MM:SS format or as duration, for example 90s, 2h30m, 3d or 1w. At timers can have more times separated by comma. Mandatory for create action, except cron timers.
+        #[arg(short = 'I', long, default_value_t = String::new())]
+        #[arg(value_parser = validate_interval)]
         interval: String,
 
+        /// Cron expression, for example "*/15 8-18 * * 1-5". Mandatory for cron timers.
+        #[arg(short, long, default_value_t = String::new())]
+        schedule: String,
+
         /// Command that timer execute. Mandatory for create action.
         #[arg(short, long)]
         command: String,
@@ -65,6 +70,36 @@ pub enum Action {
         #[arg(short, long, default_value_t = String::from("XXXXXXX"))]
         #[arg(value_parser = validate_days)]
         days: String,
+
+        /// Maximum runtime of command in HH:MM:SS format or as duration (e.g. 30m), after that it is killed. Optional for create action.
+        #[arg(short = 'T', long, default_value_t = String::new())]
+        #[arg(value_parser = validate_duration)]
+        timeout: String,
+
+        /// Time zone where the timer is scheduled, for example Europe/Budapest. Default is the time zone of server. Optional for create action.
+        #[arg(short = 'z', long, default_value_t = String::new())]
+        timezone: String,
+
+        /// Timer does not run before this time, in "YYYY-MM-DD HH:MM:SS" format. Optional for create action.
+        #[arg(long, default_value_t = String::new())]
+        not_before: String,
+
+        /// Timer does not run after this time and it is purged, in "YYYY-MM-DD HH:MM:SS" format. Optional for create action.
+        #[arg(long, default_value_t = String::new())]
+        not_after: String,
+
+        /// Timer is purged after this many runs. Optional for create action, except oneshot timers.
+        #[arg(long, default_value_t = String::new())]
+        max_runs: String,
+
+        /// Id of timer whose finished run triggers this timer. Mandatory for after timers.
+        #[arg(long, default_value_t = String::new())]
+        after: String,
+
+        /// Which result of the preceding timer triggers this timer: success, failure or any. Optional for after timers.
+        #[arg(long, default_value_t = String::new())]
+        #[arg(value_parser = validate_on)]
+        on: String,
     },
 
     /// Refresh static timer
@@ -73,11 +108,43 @@ pub enum Action {
         #[arg(short, long)]
         id: String,
     },
+
+    /// Pause active timer, it is kept but it does not run until it is resumed
+    Pause {
+        /// Timer identifier
+        #[arg(short, long)]
+        id: String,
+    },
+
+    /// Resume paused timer
+    Resume {
+        /// Timer identifier
+        #[arg(short, long)]
+        id: String,
+    },
+
+    /// Run active or static timer right now
+    Run {
+        /// Timer identifier
+        #[arg(short, long)]
+        id: String,
+    },
+
+    /// Read config again and reload static timers from timer directory
+    Reload,
 }
 
 fn validate_type(s: &str) -> Result<String, String> {
-    if s != "at" && s != "every" && s != "oneshot" {
-        return Err(String::from("Type can be only: at, every or oneshot"));
+    if s != "at" && s != "every" && s != "oneshot" && s != "cron" && s != "after" {
+        return Err(String::from("Type can be only: at, every, oneshot, cron or after"));
+    }
+
+    return Ok(String::from(s));
+}
+
+fn validate_on(s: &str) -> Result<String, String> {
+    if !s.is_empty() && s != "success" && s != "failure" && s != "any" {
+        return Err(String::from("Condition can be only: success, failure or any"));
     }
 
     return Ok(String::from(s));
@@ -97,6 +164,28 @@ fn validate_days(s: &str) -> Result<String, String> {
     return Ok(String::from(s));
 }
 
+/// Check interval, it is a duration or a list of durations separated by comma (times of day of at timers)
+fn validate_interval(s: &str) -> Result<String, String> {
+    for item in s.split(',') {
+        validate_duration(item.trim())?;
+    }
+
+    return Ok(String::from(s));
+}
+
+/// Check duration, it can be in HH:MM:SS format or number and unit pairs like 90s, 2h30m, 3d or 1w
+///
+/// The parser of Chronos is used, so the client accepts the same durations as the server.
+fn validate_duration(s: &str) -> Result<String, String> {
+    // Empty value means that it is not specified
+    if s.is_empty() {
+        return Ok(String::new());
+    }
+
+    crate::duration::parse_duration(s)?;
+    return Ok(String::from(s));
+}
+
 fn check_hostname(s: &str) -> Result<String, String> {
     if !s.starts_with("http://") && !s.starts_with("https://") && !s.starts_with("cfg://") {
         return Err(String::from("Protocol for hostname can be http:// or https:// or cfg://. "));
@@ -4,7 +4,7 @@ use tonic::{Request, Response, Status};
 use std::process::exit;
 
 use chronos::chronos_client::{ChronosClient};
-use chronos::{Empty, TimerList, TimerIdArg, TimerArg};
+use chronos::{Empty, TimerList, TimerIdArg, TimerArg, ReloadSummary};
 
 mod chronos {
     tonic::include_proto!("chronos");
@@ -13,6 +13,11 @@ mod chronos {
 mod arg;
 use arg::{Args, Action};
 
+// Durations are parsed like in Chronos, only the parser is used
+#[path = "../../chronos/src/services/duration.rs"]
+#[allow(dead_code)]
+mod duration;
+
 fn main() {
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
@@ -40,13 +45,21 @@ async fn main_asnyc() -> Result<i32, Box<dyn std::error::Error>> {
     let mut final_rc = 0;
 
     match args.action {
-        Action::Create { ref id, ref r#type, ref interval, ref command, ref days } => {
+        Action::Create { ref id, ref r#type, ref interval, ref schedule, ref command, ref days, ref timeout, ref timezone, ref not_before, ref not_after, ref max_runs, ref after, ref on } => {
             let parms = TimerArg {
                 id: id.clone(),
                 r#type: r#type.clone(),
                 interval: interval.clone(),
                 command: command.clone(),
                 days: days.clone(),
+                schedule: schedule.clone(),
+                timeout: timeout.clone(),
+                timezone: timezone.clone(),
+                not_before: not_before.clone(),
+                not_after: not_after.clone(),
+                max_runs: max_runs.clone(),
+                after: after.clone(),
+                on: on.clone(),
             };
             let response: Result<Response<Empty>, Status> = grpc_client.create_timer(Request::new(parms)).await;
             match response {
@@ -68,23 +81,34 @@ async fn main_asnyc() -> Result<i32, Box<dyn std::error::Error>> {
                     timers.sort_by(|a, b| a.next_hit.cmp(&b.next_hit));
 
                     let mut width_id = 2;
+                    let mut width_interval = 8;
+                    let mut width_zone = 4;
                     let mut width_command = 7;
 
                     for timer in &timers {
                         if timer.id.len() > width_id {
                             width_id = timer.id.len();
                         }
+                        if timer.interval.len() > width_interval {
+                            width_interval = timer.interval.len();
+                        }
+                        if timer.timezone.len() > width_zone {
+                            width_zone = timer.timezone.len();
+                        }
                         if timer.command.len() > width_command {
                             width_command = timer.command.len();
                         }
                     }
 
-                    println!("{:^w_id$} | {:^7} | {:^8} | {:^19} | {:^7} | {:^1} | {:<w_cmd$}", "ID", "Type", "Period", "Next run", "Days", "D", "Command", w_id = width_id, w_cmd = width_command);
-                    println!("{:-<w_id$} + {:-<7} + {:-<8} + {:-<19} + {:-<7} + {:-<1} + {:-<w_cmd$}", "", "", "", "", "", "", "", w_id = width_id, w_cmd = width_command);
+                    println!("{:^w_id$} | {:^7} | {:^w_int$} | {:^19} | {:^w_zone$} | {:^19} | {:^7} | {:^1} | {:^1} | {:^3} | {:^4} | {:^5} | {:^5} | {:^5} | {:<w_cmd$}", "ID", "Type", "Period", "Next run", "Zone", "Next run in zone", "Days", "D", "P", "Run", "Wait", "Skip", "Repl", "Left", "Command", w_id = width_id, w_int = width_interval, w_zone = width_zone, w_cmd = width_command);
+                    println!("{:-<w_id$} + {:-<7} + {:-<w_int$} + {:-<19} + {:-<w_zone$} + {:-<19} + {:-<7} + {:-<1} + {:-<1} + {:-<3} + {:-<4} + {:-<5} + {:-<5} + {:-<5} + {:-<w_cmd$}", "", "", "", "", "", "", "", "", "", "", "", "", "", "", "", w_id = width_id, w_int = width_interval, w_zone = width_zone, w_cmd = width_command);
 
                     for timer in timers {
                         let r#dyn = if timer.dynamic { "Y" } else { "N" };
-                        println!("{:w_id$} | {:7} | {:8} | {:19} | {:7} | {:1} | {:w_cmd$}", timer.id, timer.r#type, timer.interval, timer.next_hit, timer.days, r#dyn, timer.command, w_id = width_id, w_cmd = width_command);
+                        let paused = if timer.paused { "Y" } else { "N" };
+                        let zone = if timer.timezone.is_empty() { "-" } else { timer.timezone.as_str() };
+                        let left = if timer.remaining_runs < 0 { String::from("-") } else { timer.remaining_runs.to_string() };
+                        println!("{:w_id$} | {:7} | {:w_int$} | {:19} | {:w_zone$} | {:19} | {:7} | {:1} | {:1} | {:>3} | {:>4} | {:>5} | {:>5} | {:>5} | {:w_cmd$}", timer.id, timer.r#type, timer.interval, timer.next_hit, zone, timer.next_hit_zone, timer.days, r#dyn, paused, timer.running, timer.waiting, timer.skipped, timer.replaced, left, timer.command, w_id = width_id, w_int = width_interval, w_zone = width_zone, w_cmd = width_command);
                     }
                 }
                 Err(e) => {
@@ -101,23 +125,27 @@ async fn main_asnyc() -> Result<i32, Box<dyn std::error::Error>> {
                     let timers = timers.timers;
 
                     let mut width_id = 2;
+                    let mut width_interval = 8;
                     let mut width_command = 7;
 
                     for timer in &timers {
                         if timer.id.len() > width_id {
                             width_id = timer.id.len();
                         }
+                        if timer.interval.len() > width_interval {
+                            width_interval = timer.interval.len();
+                        }
                         if timer.command.len() > width_command {
                             width_command = timer.command.len();
                         }
                     }
 
-                    println!("{:^w_id$} | {:^7} | {:^8} | {:^7} | {:^1} | {:<w_cmd$}", "ID", "Type", "Period", "Days", "D", "Command", w_id = width_id, w_cmd = width_command);
-                    println!("{:-<w_id$} + {:-<7} + {:-<8} + {:-<7} + {:-<1} + {:-<w_cmd$}", "", "", "", "", "", "", w_id = width_id, w_cmd = width_command);
+                    println!("{:^w_id$} | {:^7} | {:^w_int$} | {:^7} | {:^1} | {:<w_cmd$}", "ID", "Type", "Period", "Days", "D", "Command", w_id = width_id, w_int = width_interval, w_cmd = width_command);
+                    println!("{:-<w_id$} + {:-<7} + {:-<w_int$} + {:-<7} + {:-<1} + {:-<w_cmd$}", "", "", "", "", "", "", w_id = width_id, w_int = width_interval, w_cmd = width_command);
 
                     for timer in timers {
                         let r#dyn = if timer.dynamic { "Y" } else { "N" };
-                        println!("{:w_id$} | {:7} | {:8} | {:7} | {:1} | {:w_cmd$}", timer.id, timer.r#type, timer.interval, timer.days, r#dyn, timer.command, w_id = width_id, w_cmd = width_command);
+                        println!("{:w_id$} | {:7} | {:w_int$} | {:7} | {:1} | {:w_cmd$}", timer.id, timer.r#type, timer.interval, timer.days, r#dyn, timer.command, w_id = width_id, w_int = width_interval, w_cmd = width_command);
                     }
                 }
                 Err(e) => {
@@ -150,6 +178,63 @@ async fn main_asnyc() -> Result<i32, Box<dyn std::error::Error>> {
                 }
             }
         }
+        Action::Pause { ref id } => {
+            let response: Result<Response<Empty>, Status> = grpc_client.pause_timer(Request::new(TimerIdArg { id: id.clone() })).await;
+            match response {
+                Ok(_) => {
+                    println!("Timer is paused");
+                }
+                Err(e) => {
+                    eprintln!("Failed request: {}", e.message());
+                    final_rc = 4;
+                }
+            }
+        }
+        Action::Resume { ref id } => {
+            let response: Result<Response<Empty>, Status> = grpc_client.resume_timer(Request::new(TimerIdArg { id: id.clone() })).await;
+            match response {
+                Ok(_) => {
+                    println!("Timer is resumed");
+                }
+                Err(e) => {
+                    eprintln!("Failed request: {}", e.message());
+                    final_rc = 4;
+                }
+            }
+        }
+        Action::Run { ref id } => {
+            let response: Result<Response<Empty>, Status> = grpc_client.trigger_timer(Request::new(TimerIdArg { id: id.clone() })).await;
+            match response {
+                Ok(_) => {
+                    println!("Timer is started");
+                }
+                Err(e) => {
+                    eprintln!("Failed request: {}", e.message());
+                    final_rc = 4;
+                }
+            }
+        }
+        Action::Reload => {
+            let response: Result<Response<ReloadSummary>, Status> = grpc_client.reload_config(Request::new(Empty {})).await;
+            match response {
+                Ok(response) => {
+                    let summary = response.into_inner();
+                    println!("Config is reloaded: {} added, {} updated, {} removed, {} failed", summary.added.len(), summary.updated.len(), summary.removed.len(), summary.failed.len());
+                    for (action, ids) in [("Added", &summary.added), ("Updated", &summary.updated), ("Removed", &summary.removed), ("Failed", &summary.failed)] {
+                        for id in ids {
+                            println!("{:7} {}", action, id);
+                        }
+                    }
+                    if !summary.failed.is_empty() {
+                        final_rc = 4;
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Failed request: {}", e.message());
+                    final_rc = 4;
+                }
+            }
+        }
         Action::VerboseLogOff => {
             let response: Result<Response<Empty>, Status> = grpc_client.verbose_log_off(Request::new(Empty {})).await;
             match response {